@@ -1,23 +1,426 @@
-#[derive(Debug, PartialEq)]
+pub mod cancel;
+pub mod clock;
+pub mod command;
+pub mod control;
+pub mod cron;
+pub mod dag;
+#[cfg(test)]
+pub mod harness;
+pub mod journal;
+pub mod logs;
+pub mod manager;
+pub mod metrics;
+pub mod progress;
+pub mod queue;
+pub mod rate;
+pub mod report;
+pub mod resources;
+pub mod retry;
+pub mod saga;
+pub mod spec;
+pub mod timeout;
+
+use cancel::{CancelReason, CancellationToken};
+use clock::Clock;
+use journal::{Journal, JournalEntry};
+use logs::JobLogs;
+use progress::{GroupProgress, JobEvent, Subscribers};
+use rate::RateLimiter;
+use report::JobReport;
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JobStatus {
     Pending,
     Running,
+    Paused,
     Stopped,
     Failed,
     Completed,
 }
 
+impl FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "Pending" => Ok(JobStatus::Pending),
+            "Running" => Ok(JobStatus::Running),
+            "Paused" => Ok(JobStatus::Paused),
+            "Stopped" => Ok(JobStatus::Stopped),
+            "Failed" => Ok(JobStatus::Failed),
+            "Completed" => Ok(JobStatus::Completed),
+            _ => Err(format!("unknown job status '{}'", value)),
+        }
+    }
+}
+
+impl JobStatus {
+    // The transition table: every status a job may move to from this one.
+    pub fn next_statuses(self) -> &'static [JobStatus] {
+        match self {
+            JobStatus::Pending => &[JobStatus::Running],
+            JobStatus::Running => &[
+                JobStatus::Completed,
+                JobStatus::Failed,
+                JobStatus::Stopped,
+                JobStatus::Paused,
+            ],
+            JobStatus::Paused => &[JobStatus::Running, JobStatus::Stopped],
+            JobStatus::Stopped | JobStatus::Failed => &[JobStatus::Running],
+            JobStatus::Completed => &[],
+        }
+    }
+
+    pub fn can_transition_to(self, to: JobStatus) -> bool {
+        self.next_statuses().contains(&to)
+    }
+
+    pub fn transition_to(self, to: JobStatus) -> Result<JobStatus, JobError> {
+        if self.can_transition_to(to) {
+            Ok(to)
+        } else {
+            Err(JobError::InvalidTransition { from: self, to })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobError {
+    InvalidTransition { from: JobStatus, to: JobStatus },
+    DuplicateJob(u32),
+    UnknownDependency { job: u32, dependency: u32 },
+    DependencyCycle(Vec<u32>),
+    Journal(String),
+    InvalidSchedule(String),
+    NotPaused(JobStatus),
+    UnknownJob(u32),
+    Panicked(String),
+    MismatchedId { key: u32, job: u32 },
+    Unrecoverable(String),
+}
+
+impl Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::InvalidTransition { from, to } => {
+                write!(f, "invalid job transition from {:?} to {:?}", from, to)
+            }
+            JobError::DuplicateJob(id) => write!(f, "job {} is already registered", id),
+            JobError::UnknownDependency { job, dependency } => {
+                write!(f, "job {} depends on unknown job {}", job, dependency)
+            }
+            JobError::DependencyCycle(cycle) => {
+                let path: Vec<String> = cycle.iter().map(|id| id.to_string()).collect();
+                write!(f, "dependency cycle between jobs {}", path.join(" -> "))
+            }
+            JobError::Journal(error) => write!(f, "job journal error: {}", error),
+            JobError::InvalidSchedule(reason) => write!(f, "invalid schedule: {}", reason),
+            JobError::NotPaused(status) => {
+                write!(f, "only paused jobs can be resumed, not {:?}", status)
+            }
+            JobError::UnknownJob(id) => write!(f, "no job with id {}", id),
+            JobError::Panicked(message) => write!(f, "job panicked: {}", message),
+            JobError::MismatchedId { key, job } => {
+                write!(f, "job {} cannot be added under id {}", job, key)
+            }
+            JobError::Unrecoverable(reason) => {
+                write!(f, "job tree cannot be rebuilt: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+// The shape of a job tree and the status of every job in it, without the work itself.
+// Groups have no id of their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobOutline {
+    pub id: Option<u32>,
+    pub status: JobStatus,
+    pub children: Vec<JobOutline>,
+    // How a group runs its children; `None` for leaf jobs.
+    pub kind: Option<GroupKind>,
+}
+
+// The settings a group was built with, so a recovered tree runs the way the original did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupKind {
+    Sequence {
+        rules: StatusRules,
+        max_concurrency: usize,
+        failure_policy: FailurePolicy,
+        // Rate limiters are shared with other groups, so only their presence is known.
+        rate_limited: bool,
+    },
+    // Every child's key in the graph with the keys it depends on, in child order.
+    Graph {
+        max_concurrency: usize,
+        edges: Vec<(u32, Vec<u32>)>,
+    },
+}
+
+impl Default for GroupKind {
+    // A plain `MultipleJob::new`.
+    fn default() -> Self {
+        GroupKind::Sequence {
+            rules: StatusRules::default(),
+            max_concurrency: 1,
+            failure_policy: FailurePolicy::ContinueOnError,
+            rate_limited: false,
+        }
+    }
+}
+
+impl JobOutline {
+    pub fn leaf(id: u32, status: JobStatus) -> Self {
+        JobOutline {
+            id: Some(id),
+            status,
+            children: Vec::new(),
+            kind: None,
+        }
+    }
+
+    pub fn group(status: JobStatus, children: Vec<JobOutline>) -> Self {
+        JobOutline {
+            id: None,
+            status,
+            children,
+            kind: Some(GroupKind::default()),
+        }
+    }
+
+    pub fn with_kind(mut self, kind: GroupKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.id.is_none()
+    }
+
+    // Ids of every leaf job in the tree, in tree order.
+    pub fn ids(&self) -> Vec<u32> {
+        match self.id {
+            Some(id) => vec![id],
+            None => self.children.iter().flat_map(JobOutline::ids).collect(),
+        }
+    }
+
+    // Ids of every leaf job in the tree that currently has `status`, in tree order.
+    pub fn ids_with_status(&self, status: JobStatus) -> Vec<u32> {
+        let mut ids = Vec::new();
+        self.collect_ids(status, &mut ids);
+        ids
+    }
+
+    fn collect_ids(&self, status: JobStatus, ids: &mut Vec<u32>) {
+        match self.id {
+            Some(id) if self.status == status => ids.push(id),
+            Some(_) => {}
+            None => {
+                for child in &self.children {
+                    child.collect_ids(status, ids);
+                }
+            }
+        }
+    }
+}
+
+// What a running job can see about the run it belongs to. Groups pass their context on
+// to their children, so cancelling the root reaches every job in the tree.
+#[derive(Clone, Default)]
+pub struct JobContext {
+    cancellation: CancellationToken,
+    journal: Option<Arc<Journal>>,
+    // Position of the job in the tree: the child index taken at each level below the root.
+    path: Vec<usize>,
+    subscribers: Subscribers,
+    logs: JobLogs,
+    // The group this job is a direct child of, which averages its children's progress.
+    group: Option<Arc<GroupProgress>>,
+    // Resources held by this job's ancestors (see `resources::Guarded`).
+    resources: Vec<String>,
+    // The last status recorded for each job during this run, shared by the whole tree.
+    recorded: Arc<Mutex<HashMap<Vec<usize>, JobStatus>>>,
+}
+
+impl JobContext {
+    pub fn new(cancellation: CancellationToken) -> Self {
+        JobContext {
+            cancellation,
+            ..JobContext::default()
+        }
+    }
+
+    pub fn with_journal(mut self, journal: Arc<Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn with_subscribers(mut self, subscribers: Subscribers) -> Self {
+        self.subscribers = subscribers;
+        self
+    }
+
+    pub fn with_logs(mut self, logs: JobLogs) -> Self {
+        self.logs = logs;
+        self
+    }
+
+    // The context for the `index`-th child of the job that owns this context.
+    pub fn child(&self, index: usize) -> JobContext {
+        let mut child = self.clone();
+        child.path.push(index);
+        child.group = None;
+        child
+    }
+
+    // Starts tracking the progress of the current job's children, one entry per child.
+    pub fn progress_group(&self, percents: Vec<f64>) -> Arc<GroupProgress> {
+        Arc::new(GroupProgress::new(
+            self.path.clone(),
+            percents,
+            self.group.clone(),
+            self.subscribers.clone(),
+        ))
+    }
+
+    // Like `child`, but progress the child reports also counts towards `group`.
+    pub fn child_in_group(&self, index: usize, group: &Arc<GroupProgress>) -> JobContext {
+        let mut child = self.child(index);
+        child.group = Some(Arc::clone(group));
+        child
+    }
+
+    // Writes a status transition of the current job to the journal, if there is one, and
+    // tells subscribers. A journal that cannot be written must not fail the job it describes.
+    // Recording the status the job already has is not a transition and is ignored.
+    pub fn record(&self, status: JobStatus) {
+        let previous = self
+            .recorded
+            .lock()
+            .unwrap()
+            .insert(self.path.clone(), status);
+        if previous == Some(status) {
+            return;
+        }
+        if let Some(journal) = &self.journal {
+            let _ = journal.append(&JournalEntry::Transition {
+                path: self.path.clone(),
+                status,
+            });
+        }
+        // `Job` publishes the root's transitions itself, with the status it left.
+        if !self.path.is_empty() {
+            self.subscribers.publish(&JobEvent::StatusChanged {
+                path: self.path.clone(),
+                from: None,
+                to: status,
+            });
+        }
+    }
+
+    // Publishes how far along the current job is, from 0 to 100 percent.
+    pub fn report_progress(&self, percent: f64, message: impl Into<String>) {
+        let percent = percent.clamp(0.0, 100.0);
+        self.subscribers.publish(&JobEvent::Progress {
+            path: self.path.clone(),
+            percent,
+            message: message.into(),
+        });
+        if let (Some(group), Some(index)) = (&self.group, self.path.last()) {
+            group.update(*index, percent);
+        }
+    }
+
+    // Adds a line to the current job's log buffer.
+    pub fn log(&self, message: impl Into<String>) {
+        self.logs.write(&self.path, self.now(), message);
+    }
+
+    // The time on the run's clock, which is the system clock unless the job was given
+    // another one.
+    pub fn now(&self) -> SystemTime {
+        self.cancellation.clock().now()
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    // A cooperative checkpoint for long-running tasks: `Err` means the task should return
+    // now, ideally after saving enough state to continue when it is run again.
+    pub fn checkpoint(&self) -> Result<(), CancelReason> {
+        match self.cancellation.reason() {
+            Some(reason) => Err(reason),
+            None => Ok(()),
+        }
+    }
+
+    pub fn with_timeout(&self, timeout: Duration) -> JobContext {
+        JobContext {
+            cancellation: self.cancellation.child_with_timeout(timeout),
+            ..self.clone()
+        }
+    }
+}
+
 // The "Implementor" trait. This is the implementation part of the bridge.
-trait JobImpl {
-    fn run(&mut self) -> String;
-    fn stop(&mut self) -> String;
-    fn status(&self) -> &JobStatus;
+// Implementors are `Send` so groups can hand their children to worker threads.
+pub trait JobImpl: Send {
+    fn run(&mut self, context: &JobContext) -> JobReport;
+    fn stop(&mut self) -> JobReport;
+    fn pause(&mut self) -> JobReport;
+    fn status(&self) -> JobStatus;
+    fn outline(&self) -> JobOutline;
+
+    // Undoes the work of a completed run, for jobs that declare how (see `saga`). Returns
+    // `None` when there is nothing to undo.
+    fn compensate(&mut self, _context: &JobContext) -> Option<JobReport> {
+        None
+    }
+}
+
+// Wraps the compensations a group ran for its children; the group's compensation fails
+// if any of theirs did.
+fn compensation_group(output: &str, compensations: Vec<JobReport>) -> Option<JobReport> {
+    if compensations.is_empty() {
+        return None;
+    }
+    let status = if compensations
+        .iter()
+        .all(|report| report.status == JobStatus::Completed)
+    {
+        JobStatus::Completed
+    } else {
+        JobStatus::Failed
+    };
+    Some(JobReport::new(None, status, output).with_children(compensations))
 }
 
+// The unit of work a `SingleJob` executes. Errors are rendered to strings up front so
+// jobs with different error types can live in the same tree.
+type Task = Box<dyn FnMut(&JobContext) -> Result<String, String> + Send>;
+
 // A concrete "Implementor".
 pub struct SingleJob {
     id: u32,
     status: JobStatus,
+    task: Option<Task>,
 }
 
 impl SingleJob {
@@ -25,23 +428,194 @@ impl SingleJob {
         SingleJob {
             id,
             status: JobStatus::Pending,
+            task: None,
+        }
+    }
+
+    pub fn with_task<F, E>(id: u32, mut task: F) -> Self
+    where
+        F: FnMut() -> Result<String, E> + Send + 'static,
+        E: Display,
+    {
+        Self::with_context_task(id, move |_: &JobContext| task())
+    }
+
+    // Like `with_task`, but the closure sees the run's context so long-running work can
+    // check for cancellation and return early.
+    pub fn with_context_task<F, E>(id: u32, mut task: F) -> Self
+    where
+        F: FnMut(&JobContext) -> Result<String, E> + Send + 'static,
+        E: Display,
+    {
+        SingleJob {
+            id,
+            status: JobStatus::Pending,
+            task: Some(Box::new(move |context| {
+                task(context).map_err(|e| e.to_string())
+            })),
+        }
+    }
+
+    // A job without work that picks up where a previous process left it.
+    fn restored(id: u32, status: JobStatus) -> Self {
+        SingleJob {
+            id,
+            status,
+            task: None,
+        }
+    }
+
+    fn execute(&mut self, context: &JobContext) -> String {
+        self.status = JobStatus::Running;
+        let Some(task) = self.task.as_mut() else {
+            return format!("Running single job {}", self.id);
+        };
+
+        let result = task(context);
+        match context.cancellation().reason() {
+            // A task that finished before noticing the stop or pause has done its work.
+            Some(CancelReason::Stopped) if result.is_err() => {
+                self.status = JobStatus::Stopped;
+                return format!("Single job {} was stopped", self.id);
+            }
+            Some(CancelReason::Paused) if result.is_err() => {
+                self.status = JobStatus::Paused;
+                return format!("Single job {} was paused", self.id);
+            }
+            Some(CancelReason::TimedOut(timeout)) => {
+                self.status = JobStatus::Failed;
+                return format!("Single job {} timed out after {:?}", self.id, timeout);
+            }
+            _ => {}
+        }
+
+        match result {
+            Ok(output) => {
+                self.status = JobStatus::Completed;
+                format!("Single job {} completed: {}", self.id, output)
+            }
+            Err(error) => {
+                self.status = JobStatus::Failed;
+                format!("Single job {} failed: {}", self.id, error)
+            }
         }
     }
 }
 
 impl JobImpl for SingleJob {
-    fn run(&mut self) -> String {
-        self.status = JobStatus::Running;
-        format!("Running single job {}", self.id)
+    fn run(&mut self, context: &JobContext) -> JobReport {
+        let started_at = context.now();
+        context.record(JobStatus::Running);
+        let output = self.execute(context);
+        context.record(self.status);
+        JobReport::new(Some(self.id), self.status, output).with_span(started_at, context.now())
     }
 
-    fn stop(&mut self) -> String {
+    fn stop(&mut self) -> JobReport {
         self.status = JobStatus::Stopped;
-        format!("Stopping single job {}", self.id)
+        JobReport::new(
+            Some(self.id),
+            self.status,
+            format!("Stopping single job {}", self.id),
+        )
+    }
+
+    fn pause(&mut self) -> JobReport {
+        if self.status == JobStatus::Running {
+            self.status = JobStatus::Paused;
+        }
+        JobReport::new(
+            Some(self.id),
+            self.status,
+            format!("Pausing single job {}", self.id),
+        )
+    }
+
+    fn status(&self) -> JobStatus {
+        self.status
+    }
+
+    fn outline(&self) -> JobOutline {
+        JobOutline::leaf(self.id, self.status)
+    }
+}
+
+// Decides which status a `MultipleJob` reports from the statuses of its children.
+// A status shared by every child always wins; otherwise the first status in
+// `precedence` held by any child is reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusRules {
+    precedence: Vec<JobStatus>,
+}
+
+impl StatusRules {
+    pub fn new(precedence: Vec<JobStatus>) -> Self {
+        StatusRules { precedence }
+    }
+
+    pub fn rollup(&self, statuses: &[JobStatus]) -> Option<JobStatus> {
+        let first = *statuses.first()?;
+        if statuses.iter().all(|status| *status == first) {
+            return Some(first);
+        }
+
+        self.precedence
+            .iter()
+            .find(|status| statuses.contains(status))
+            .copied()
+            // Mixed statuses that the rules do not mention are still in progress.
+            .or(Some(JobStatus::Running))
     }
+}
 
-    fn status(&self) -> &JobStatus {
-        &self.status
+impl Default for StatusRules {
+    // Any failure fails the group, and it only completes once every child has.
+    fn default() -> Self {
+        StatusRules::new(vec![
+            JobStatus::Failed,
+            JobStatus::Running,
+            JobStatus::Paused,
+            JobStatus::Stopped,
+            JobStatus::Pending,
+        ])
+    }
+}
+
+// What a `MultipleJob` does once one of its children fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    // Skip every child that has not started yet.
+    FailFast,
+    // Run every child and fail the group if any of them failed.
+    ContinueOnError,
+    // Keep going, and keep the group successful, until more than N children have failed.
+    TolerateFailures(usize),
+}
+
+impl FailurePolicy {
+    fn should_stop(self, failures: usize) -> bool {
+        match self {
+            FailurePolicy::FailFast => failures > 0,
+            FailurePolicy::ContinueOnError => false,
+            FailurePolicy::TolerateFailures(limit) => failures > limit,
+        }
+    }
+
+    fn tolerates(self, failures: usize) -> bool {
+        match self {
+            FailurePolicy::TolerateFailures(limit) => failures <= limit,
+            _ => failures == 0,
+        }
+    }
+}
+
+impl Display for FailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailurePolicy::FailFast => write!(f, "fail-fast"),
+            FailurePolicy::ContinueOnError => write!(f, "continue-on-error"),
+            FailurePolicy::TolerateFailures(limit) => write!(f, "tolerate {} failures", limit),
+        }
     }
 }
 
@@ -49,6 +623,10 @@ impl JobImpl for SingleJob {
 pub struct MultipleJob {
     jobs: Vec<Box<dyn JobImpl>>,
     status: JobStatus,
+    rules: StatusRules,
+    max_concurrency: usize,
+    failure_policy: FailurePolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl MultipleJob {
@@ -56,48 +634,383 @@ impl MultipleJob {
         MultipleJob {
             jobs,
             status: JobStatus::Pending,
+            rules: StatusRules::default(),
+            max_concurrency: 1,
+            failure_policy: FailurePolicy::ContinueOnError,
+            rate_limiter: None,
+        }
+    }
+
+    pub fn with_status_rules(mut self, rules: StatusRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    // Runs up to `max_concurrency` children at once; 1 (the default) runs them in order.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    pub fn with_failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    // Every child waits for the limiter before it starts, in parallel mode too.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    // Returns one entry per child, or `None` for children that did not run: those skipped
+    // by the failure policy or by cancellation, and those that had already completed.
+    fn run_children(&mut self, context: &JobContext) -> Vec<Option<JobReport>> {
+        let mut results = vec![None; self.jobs.len()];
+        let workers = self.max_concurrency.min(self.jobs.len());
+        let policy = self.failure_policy;
+        let rate_limiter = self.rate_limiter.as_deref();
+        let failures = AtomicUsize::new(0);
+        let progress = context.progress_group(
+            self.jobs
+                .iter()
+                .map(|job| match job.status() {
+                    JobStatus::Completed => 100.0,
+                    _ => 0.0,
+                })
+                .collect(),
+        );
+
+        // Workers pull the next child off a shared queue and tag each result with the
+        // child's position, so the output order does not depend on thread scheduling.
+        let queue = Mutex::new(
+            self.jobs
+                .iter_mut()
+                .enumerate()
+                .filter(|(_, job)| job.status() != JobStatus::Completed),
+        );
+        let work = || {
+            let mut finished = Vec::new();
+            while !policy.should_stop(failures.load(Ordering::SeqCst)) && !context.is_cancelled() {
+                let next = queue.lock().unwrap().next();
+                let Some((index, job)) = next else {
+                    break;
+                };
+                // A child still waiting for the limiter when the run is cancelled never starts.
+                if let Some(limiter) = rate_limiter
+                    && limiter.acquire(context.cancellation()).is_err()
+                {
+                    break;
+                }
+                let report = job.run(&context.child_in_group(index, &progress));
+                match job.status() {
+                    JobStatus::Failed => {
+                        failures.fetch_add(1, Ordering::SeqCst);
+                    }
+                    // Children that never report progress still count once they finish.
+                    JobStatus::Completed => progress.update(index, 100.0),
+                    _ => {}
+                }
+                finished.push((index, report));
+            }
+            finished
+        };
+
+        let finished: Vec<(usize, JobReport)> = if workers <= 1 {
+            work()
+        } else {
+            thread::scope(|scope| {
+                let handles: Vec<_> = (0..workers).map(|_| scope.spawn(work)).collect();
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().unwrap())
+                    .collect()
+            })
+        };
+
+        for (index, report) in finished {
+            results[index] = Some(report);
         }
+        results
+    }
+
+    // Runs the compensations of completed children, last child first.
+    fn compensate_children(&mut self, context: &JobContext) -> Vec<JobReport> {
+        self.jobs
+            .iter_mut()
+            .enumerate()
+            .rev()
+            .filter(|(_, job)| job.status() == JobStatus::Completed)
+            .filter_map(|(index, job)| job.compensate(&context.child(index)))
+            .collect()
     }
 }
 
 impl JobImpl for MultipleJob {
-    fn run(&mut self) -> String {
+    fn run(&mut self, context: &JobContext) -> JobReport {
+        let started_at = context.now();
         self.status = JobStatus::Running;
-        let results: Vec<String> = self.jobs.iter_mut().map(|job| job.run()).collect();
-        format!("Running multiple jobs:\n{}", results.join("\n"))
+        let results = self.run_children(context);
+        match context.cancellation().reason() {
+            Some(CancelReason::Paused) => self.status = JobStatus::Paused,
+            Some(CancelReason::Stopped) => self.status = JobStatus::Stopped,
+            _ if self.jobs.is_empty() => {
+                // There is nothing to wait for, so a group without children is done at once.
+                self.status = JobStatus::Completed;
+            }
+            _ => {}
+        }
+        let skipped = results
+            .iter()
+            .zip(&self.jobs)
+            .filter(|(result, job)| result.is_none() && job.status() != JobStatus::Completed)
+            .count();
+        let children: Vec<JobReport> = results.into_iter().flatten().collect();
+
+        let failed = self
+            .jobs
+            .iter()
+            .filter(|job| job.status() == JobStatus::Failed)
+            .count();
+        // A failed group rolls back what it managed to do, saga style.
+        let compensations = if self.status() == JobStatus::Failed {
+            self.compensate_children(context)
+        } else {
+            Vec::new()
+        };
+
+        let mut report = JobReport::new(None, self.status(), "Running multiple jobs:")
+            .with_span(started_at, context.now())
+            .with_children(children)
+            .with_compensations(compensations);
+        if failed > 0 {
+            report = report.with_note(format!(
+                "Failure policy {}: {} of {} jobs failed, {} skipped",
+                self.failure_policy,
+                failed,
+                self.jobs.len(),
+                skipped
+            ));
+        }
+        let undo_failures: Vec<String> = report
+            .compensation_failures()
+            .iter()
+            .filter_map(|failure| failure.id)
+            .map(|id| id.to_string())
+            .collect();
+        if !undo_failures.is_empty() {
+            report = report.with_note(format!(
+                "Compensation failed for jobs {}; their changes were not undone",
+                undo_failures.join(", ")
+            ));
+        }
+        report
     }
 
-    fn stop(&mut self) -> String {
+    // Children that already finished keep their status, so a rerun does not repeat them.
+    fn stop(&mut self) -> JobReport {
         self.status = JobStatus::Stopped;
-        let results: Vec<String> = self.jobs.iter_mut().map(|job| job.stop()).collect();
-        format!("Stopping multiple jobs:\n{}", results.join("\n"))
+        let children: Vec<JobReport> = self
+            .jobs
+            .iter_mut()
+            .filter(|job| job.status().can_transition_to(JobStatus::Stopped))
+            .map(|job| job.stop())
+            .collect();
+        JobReport::new(None, self.status(), "Stopping multiple jobs:").with_children(children)
     }
 
-    fn status(&self) -> &JobStatus {
-        // In a real-world scenario, this might check the status of all sub-jobs.
-        &self.status
+    // Only running children are paused; the rest keep their status so that resuming
+    // skips the completed ones and starts the pending ones.
+    fn pause(&mut self) -> JobReport {
+        self.status = JobStatus::Paused;
+        let children: Vec<JobReport> = self
+            .jobs
+            .iter_mut()
+            .filter(|job| job.status() == JobStatus::Running)
+            .map(|job| job.pause())
+            .collect();
+        JobReport::new(None, self.status(), "Pausing multiple jobs:").with_children(children)
+    }
+
+    // A completed group inside a failed one undoes all of its own completed children.
+    fn compensate(&mut self, context: &JobContext) -> Option<JobReport> {
+        compensation_group(
+            "Compensating multiple jobs:",
+            self.compensate_children(context),
+        )
+    }
+
+    fn status(&self) -> JobStatus {
+        let mut statuses: Vec<JobStatus> = self.jobs.iter().map(|job| job.status()).collect();
+
+        let failures = statuses
+            .iter()
+            .filter(|status| **status == JobStatus::Failed)
+            .count();
+        if failures > 0 && self.failure_policy.tolerates(failures) {
+            for status in statuses.iter_mut().filter(|s| **s == JobStatus::Failed) {
+                *status = JobStatus::Completed;
+            }
+        }
+
+        // A group without children has nothing to derive from, so it reports its own flag.
+        let status = self.rules.rollup(&statuses).unwrap_or(self.status);
+        // A group paused or stopped between two children has none that are paused or
+        // stopped themselves.
+        if matches!(self.status, JobStatus::Paused | JobStatus::Stopped)
+            && status != JobStatus::Completed
+        {
+            return self.status;
+        }
+        status
+    }
+
+    fn outline(&self) -> JobOutline {
+        let children = self.jobs.iter().map(|job| job.outline()).collect();
+        JobOutline::group(self.status(), children).with_kind(GroupKind::Sequence {
+            rules: self.rules.clone(),
+            max_concurrency: self.max_concurrency,
+            failure_policy: self.failure_policy,
+            rate_limited: self.rate_limiter.is_some(),
+        })
     }
 }
 
 // The "Abstraction". This is the public-facing part of the bridge.
 pub struct Job {
     implementation: Box<dyn JobImpl>,
+    cancellation: CancellationToken,
+    journal: Option<Arc<Journal>>,
+    subscribers: Subscribers,
+    logs: JobLogs,
 }
 
 impl Job {
     pub fn new(implementation: Box<dyn JobImpl>) -> Self {
-        Job { implementation }
+        Job {
+            implementation,
+            cancellation: CancellationToken::new(),
+            journal: None,
+            subscribers: Subscribers::default(),
+            logs: JobLogs::default(),
+        }
+    }
+
+    // Records the job tree and every status transition in `journal`, so the tree can be
+    // rebuilt with `journal::rebuild` after a crash.
+    pub fn with_journal(mut self, journal: Arc<Journal>) -> Self {
+        self.journal = Some(journal);
+        self
     }
 
-    pub fn run(&mut self) -> String {
-        self.implementation.run()
+    // Runs the job on `clock` instead of the system clock: timeouts, retry delays, sleeps
+    // and report times all follow it.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.cancellation = CancellationToken::with_clock(clock);
+        self
+    }
+
+    // Runs the job under `cancellation`, for instance a child of a token that cancels a
+    // whole batch. The job follows that token's clock.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    // Replaces the default log buffers, for instance with ones that spill to a file.
+    pub fn with_logs(mut self, logs: JobLogs) -> Self {
+        self.logs = logs;
+        self
+    }
+
+    pub fn outline(&self) -> JobOutline {
+        self.implementation.outline()
+    }
+
+    fn write_snapshot(&self) -> Result<(), JobError> {
+        match &self.journal {
+            Some(journal) => journal
+                .snapshot(&self.outline())
+                .map_err(|error| JobError::Journal(error.to_string())),
+            None => Ok(()),
+        }
     }
 
-    pub fn stop(&mut self) -> String {
-        self.implementation.stop()
+    // A handle that stops this job from another thread while `run` is in progress.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
     }
 
-    pub fn status(&self) -> &JobStatus {
+    // Status changes and progress of this job and every job below it are published here.
+    pub fn subscribers(&self) -> Subscribers {
+        self.subscribers.clone()
+    }
+
+    // Lines logged by this job and every job below it, kept across runs.
+    pub fn logs(&self) -> JobLogs {
+        self.logs.clone()
+    }
+
+    fn publish_transition(&self, from: JobStatus, to: JobStatus) {
+        if from != to {
+            self.subscribers.publish(&JobEvent::StatusChanged {
+                path: Vec::new(),
+                from: Some(from),
+                to,
+            });
+        }
+    }
+
+    pub fn run(&mut self) -> Result<JobReport, JobError> {
+        let from = self.implementation.status();
+        from.transition_to(JobStatus::Running)?;
+        self.write_snapshot()?;
+        self.cancellation.reset();
+        let mut context = JobContext::new(self.cancellation.clone())
+            .with_subscribers(self.subscribers.clone())
+            .with_logs(self.logs.clone());
+        if let Some(journal) = &self.journal {
+            context = context.with_journal(Arc::clone(journal));
+        }
+        self.publish_transition(from, JobStatus::Running);
+        let report = self.implementation.run(&context);
+        self.publish_transition(JobStatus::Running, self.status());
+        Ok(report)
+    }
+
+    pub fn stop(&mut self) -> Result<JobReport, JobError> {
+        let from = self.implementation.status();
+        from.transition_to(JobStatus::Stopped)?;
+        self.cancellation.cancel();
+        let report = self.implementation.stop();
+        // Stopping does not go through a context, so record the resulting statuses in full.
+        self.write_snapshot()?;
+        self.publish_transition(from, self.status());
+        Ok(report)
+    }
+
+    // Pausing a job that is running on another thread goes through its cancellation
+    // token instead: `cancellation_token().pause()` makes the run return early.
+    pub fn pause(&mut self) -> Result<JobReport, JobError> {
+        let from = self.implementation.status();
+        from.transition_to(JobStatus::Paused)?;
+        self.cancellation.pause();
+        let report = self.implementation.pause();
+        self.write_snapshot()?;
+        self.publish_transition(from, self.status());
+        Ok(report)
+    }
+
+    // Runs a paused job again. Work that already completed is not repeated.
+    pub fn resume(&mut self) -> Result<JobReport, JobError> {
+        let from = self.implementation.status();
+        if from != JobStatus::Paused {
+            return Err(JobError::NotPaused(from));
+        }
+        self.run()
+    }
+
+    pub fn status(&self) -> JobStatus {
         self.implementation.status()
     }
 }
@@ -105,20 +1018,54 @@ impl Job {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use report::ReportFormat;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[test]
     fn test_single_job_lifecycle() {
         let single_job_impl = SingleJob::new(1);
-        assert_eq!(*single_job_impl.status(), JobStatus::Pending);
+        assert_eq!(single_job_impl.status(), JobStatus::Pending);
+
+        let mut job = Job::new(Box::new(single_job_impl));
+        assert_eq!(job.status(), JobStatus::Pending);
+
+        assert_eq!(job.run().unwrap().to_string(), "Running single job 1");
+        assert_eq!(job.status(), JobStatus::Running);
+
+        assert_eq!(job.stop().unwrap().to_string(), "Stopping single job 1");
+        assert_eq!(job.status(), JobStatus::Stopped);
+    }
+
+    #[test]
+    fn test_single_job_with_task_completes() {
+        let mut calls = 0;
+        let single_job_impl = SingleJob::with_task(2, move || {
+            calls += 1;
+            Ok::<_, String>(format!("call {}", calls))
+        });
 
         let mut job = Job::new(Box::new(single_job_impl));
-        assert_eq!(*job.status(), JobStatus::Pending);
+        assert_eq!(job.status(), JobStatus::Pending);
 
-        assert_eq!(job.run(), "Running single job 1");
-        assert_eq!(*job.status(), JobStatus::Running);
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Single job 2 completed: call 1"
+        );
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_single_job_with_task_fails() {
+        let single_job_impl =
+            SingleJob::with_task(3, || "not a number".parse::<u32>().map(|n| n.to_string()));
 
-        assert_eq!(job.stop(), "Stopping single job 1");
-        assert_eq!(*job.status(), JobStatus::Stopped);
+        let mut job = Job::new(Box::new(single_job_impl));
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Single job 3 failed: invalid digit found in string"
+        );
+        assert_eq!(job.status(), JobStatus::Failed);
     }
 
     #[test]
@@ -126,20 +1073,47 @@ mod tests {
         let sub_job1 = Box::new(SingleJob::new(10));
         let sub_job2 = Box::new(SingleJob::new(11));
         let multiple_job_impl = MultipleJob::new(vec![sub_job1, sub_job2]);
-        assert_eq!(*multiple_job_impl.status(), JobStatus::Pending);
+        assert_eq!(multiple_job_impl.status(), JobStatus::Pending);
 
         let mut job = Job::new(Box::new(multiple_job_impl));
-        assert_eq!(*job.status(), JobStatus::Pending);
+        assert_eq!(job.status(), JobStatus::Pending);
 
         let expected_run_output =
             "Running multiple jobs:\nRunning single job 10\nRunning single job 11";
-        assert_eq!(job.run(), expected_run_output);
-        assert_eq!(*job.status(), JobStatus::Running);
+        assert_eq!(job.run().unwrap().to_string(), expected_run_output);
+        assert_eq!(job.status(), JobStatus::Running);
 
         let expected_stop_output =
             "Stopping multiple jobs:\nStopping single job 10\nStopping single job 11";
-        assert_eq!(job.stop(), expected_stop_output);
-        assert_eq!(*job.status(), JobStatus::Stopped);
+        assert_eq!(job.stop().unwrap().to_string(), expected_stop_output);
+        assert_eq!(job.status(), JobStatus::Stopped);
+    }
+
+    #[test]
+    fn test_stopping_a_group_keeps_finished_children() {
+        let done = Box::new(SingleJob::with_task(12, || {
+            Ok::<_, String>("done".to_string())
+        }));
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![
+            done,
+            Box::new(SingleJob::new(13)),
+            Box::new(SingleJob::new(14)),
+        ])));
+        job.run().unwrap();
+
+        let report = job.stop().unwrap();
+        assert_eq!(
+            report.to_string(),
+            "Stopping multiple jobs:\nStopping single job 13\nStopping single job 14"
+        );
+        assert_eq!(
+            job.outline().ids_with_status(JobStatus::Completed),
+            vec![12]
+        );
+        assert_eq!(job.status(), JobStatus::Stopped);
+
+        let rerun = job.run().unwrap();
+        assert_eq!(rerun.children.len(), 2);
     }
 
     #[test]
@@ -151,16 +1125,393 @@ mod tests {
         let sub_job3 = Box::new(SingleJob::new(200));
 
         let outer_multiple_job_impl = MultipleJob::new(vec![inner_multiple_job, sub_job3]);
-        assert_eq!(*outer_multiple_job_impl.status(), JobStatus::Pending);
+        assert_eq!(outer_multiple_job_impl.status(), JobStatus::Pending);
 
         let mut job = Job::new(Box::new(outer_multiple_job_impl));
 
         let expected_run_output = "Running multiple jobs:\nRunning multiple jobs:\nRunning single job 100\nRunning single job 101\nRunning single job 200";
-        assert_eq!(job.run(), expected_run_output);
-        assert_eq!(*job.status(), JobStatus::Running);
+        assert_eq!(job.run().unwrap().to_string(), expected_run_output);
+        assert_eq!(job.status(), JobStatus::Running);
 
         let expected_stop_output = "Stopping multiple jobs:\nStopping multiple jobs:\nStopping single job 100\nStopping single job 101\nStopping single job 200";
-        assert_eq!(job.stop(), expected_stop_output);
-        assert_eq!(*job.status(), JobStatus::Stopped);
+        assert_eq!(job.stop().unwrap().to_string(), expected_stop_output);
+        assert_eq!(job.status(), JobStatus::Stopped);
+    }
+
+    #[test]
+    fn test_multiple_job_status_rolls_up_from_children() {
+        let ok = Box::new(SingleJob::with_task(20, || {
+            Ok::<_, String>("ok".to_string())
+        }));
+        let failing = Box::new(SingleJob::with_task(21, || Err("boom")));
+        let pending = Box::new(SingleJob::new(22));
+
+        let mut inner = MultipleJob::new(vec![ok, failing]);
+        assert_eq!(inner.status(), JobStatus::Pending);
+        inner.run(&JobContext::default());
+        assert_eq!(inner.status(), JobStatus::Failed);
+
+        let outer = MultipleJob::new(vec![Box::new(inner), pending]);
+        assert_eq!(outer.status(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_multiple_job_completes_only_when_all_children_complete() {
+        let first = Box::new(SingleJob::with_task(30, || {
+            Ok::<_, String>("a".to_string())
+        }));
+        let second = Box::new(SingleJob::with_task(31, || {
+            Ok::<_, String>("b".to_string())
+        }));
+        let mut inner = MultipleJob::new(vec![first, second]);
+        inner.run(&JobContext::default());
+        assert_eq!(inner.status(), JobStatus::Completed);
+
+        let outer = MultipleJob::new(vec![Box::new(inner), Box::new(SingleJob::new(32))]);
+        assert_eq!(outer.status(), JobStatus::Pending);
+    }
+
+    #[test]
+    fn test_multiple_job_custom_status_rules() {
+        let ok = Box::new(SingleJob::with_task(40, || {
+            Ok::<_, String>("ok".to_string())
+        }));
+        let failing = Box::new(SingleJob::with_task(41, || Err("boom")));
+        // Failures are tolerated: the group is done once nothing is left running.
+        let rules = StatusRules::new(vec![
+            JobStatus::Running,
+            JobStatus::Pending,
+            JobStatus::Completed,
+        ]);
+        let mut job = Job::new(Box::new(
+            MultipleJob::new(vec![ok, failing]).with_status_rules(rules),
+        ));
+
+        job.run().unwrap();
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_empty_multiple_job_completes_when_run() {
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![])));
+        assert_eq!(job.status(), JobStatus::Pending);
+        assert_eq!(job.run().unwrap().status, JobStatus::Completed);
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_illegal_transitions_are_rejected() {
+        let mut job = Job::new(Box::new(SingleJob::new(50)));
+        assert_eq!(
+            job.stop(),
+            Err(JobError::InvalidTransition {
+                from: JobStatus::Pending,
+                to: JobStatus::Stopped,
+            })
+        );
+        assert_eq!(job.status(), JobStatus::Pending);
+
+        let mut job = Job::new(Box::new(SingleJob::with_task(51, || {
+            Ok::<_, String>("done".to_string())
+        })));
+        job.run().unwrap();
+        let error = job.run().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid job transition from Completed to Running"
+        );
+    }
+
+    #[test]
+    fn test_failed_and_stopped_jobs_can_run_again() {
+        let mut attempts = 0;
+        let mut job = Job::new(Box::new(SingleJob::with_task(52, move || {
+            attempts += 1;
+            if attempts < 2 {
+                Err("flaky")
+            } else {
+                Ok("recovered".to_string())
+            }
+        })));
+
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Single job 52 failed: flaky"
+        );
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Single job 52 completed: recovered"
+        );
+
+        let mut job = Job::new(Box::new(SingleJob::new(53)));
+        job.run().unwrap();
+        job.stop().unwrap();
+        assert_eq!(job.run().unwrap().to_string(), "Running single job 53");
+    }
+
+    #[test]
+    fn test_parallel_multiple_job_is_bounded_and_ordered() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let children: Vec<Box<dyn JobImpl>> = (0..8)
+            .map(|id| {
+                let in_flight = Arc::clone(&in_flight);
+                let peak = Arc::clone(&peak);
+                Box::new(SingleJob::with_task(id, move || {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok::<_, String>(format!("worker {}", id))
+                })) as Box<dyn JobImpl>
+            })
+            .collect();
+
+        let mut job = Job::new(Box::new(MultipleJob::new(children).with_max_concurrency(3)));
+        let output = job.run().unwrap().to_string();
+
+        let expected: Vec<String> = (0..8)
+            .map(|id| format!("Single job {} completed: worker {}", id, id))
+            .collect();
+        assert_eq!(
+            output,
+            format!("Running multiple jobs:\n{}", expected.join("\n"))
+        );
+        assert_eq!(job.status(), JobStatus::Completed);
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(peak.load(Ordering::SeqCst) > 1);
+    }
+
+    fn succeeding(id: u32) -> Box<dyn JobImpl> {
+        Box::new(SingleJob::with_task(id, || {
+            Ok::<_, String>("ok".to_string())
+        }))
+    }
+
+    fn failing(id: u32) -> Box<dyn JobImpl> {
+        Box::new(SingleJob::with_task(id, || Err("boom")))
+    }
+
+    #[test]
+    fn test_fail_fast_skips_remaining_children() {
+        let mut job = Job::new(Box::new(
+            MultipleJob::new(vec![succeeding(60), failing(61), succeeding(62)])
+                .with_failure_policy(FailurePolicy::FailFast),
+        ));
+
+        let expected_run_output = "Running multiple jobs:\nSingle job 60 completed: ok\nSingle job 61 failed: boom\nFailure policy fail-fast: 1 of 3 jobs failed, 1 skipped";
+        assert_eq!(job.run().unwrap().to_string(), expected_run_output);
+        assert_eq!(job.status(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_continue_on_error_runs_every_child() {
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![
+            failing(70),
+            succeeding(71),
+            failing(72),
+        ])));
+
+        let expected_run_output = "Running multiple jobs:\nSingle job 70 failed: boom\nSingle job 71 completed: ok\nSingle job 72 failed: boom\nFailure policy continue-on-error: 2 of 3 jobs failed, 0 skipped";
+        assert_eq!(job.run().unwrap().to_string(), expected_run_output);
+        assert_eq!(job.status(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_tolerated_failures_keep_group_successful() {
+        let mut job = Job::new(Box::new(
+            MultipleJob::new(vec![failing(80), succeeding(81)])
+                .with_failure_policy(FailurePolicy::TolerateFailures(1)),
+        ));
+        job.run().unwrap();
+        assert_eq!(job.status(), JobStatus::Completed);
+
+        let mut job = Job::new(Box::new(
+            MultipleJob::new(vec![failing(82), failing(83), succeeding(84)])
+                .with_failure_policy(FailurePolicy::TolerateFailures(1)),
+        ));
+        let output = job.run().unwrap().to_string();
+        assert!(
+            output.ends_with("Failure policy tolerate 1 failures: 2 of 3 jobs failed, 1 skipped")
+        );
+        assert_eq!(job.status(), JobStatus::Failed);
+    }
+
+    fn wait_for_cancellation(id: u32) -> Box<dyn JobImpl> {
+        Box::new(SingleJob::with_context_task(id, |context: &JobContext| {
+            context.cancellation().sleep(Duration::from_secs(30));
+            context.checkpoint().map_err(|reason| reason.to_string())?;
+            Ok::<_, String>("finished".to_string())
+        }))
+    }
+
+    #[test]
+    fn test_task_that_finished_before_a_stop_stays_completed() {
+        // The stop arrives after the task's work is done but before it returns.
+        let finishing = SingleJob::with_context_task(94, |context: &JobContext| {
+            context.cancellation().cancel();
+            Ok::<_, String>("done".to_string())
+        });
+        let mut job = Job::new(Box::new(finishing));
+
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Single job 94 completed: done"
+        );
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_cancellation_token_stops_running_job() {
+        let mut job = Job::new(wait_for_cancellation(90));
+        let token = job.cancellation_token();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+
+        assert_eq!(job.run().unwrap().to_string(), "Single job 90 was stopped");
+        assert_eq!(job.status(), JobStatus::Stopped);
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn test_cancellation_propagates_to_all_children() {
+        let mut job = Job::new(Box::new(
+            MultipleJob::new(vec![
+                wait_for_cancellation(91),
+                wait_for_cancellation(92),
+                wait_for_cancellation(93),
+            ])
+            .with_max_concurrency(2),
+        ));
+        let token = job.cancellation_token();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+
+        let expected_run_output =
+            "Running multiple jobs:\nSingle job 91 was stopped\nSingle job 92 was stopped";
+        assert_eq!(job.run().unwrap().to_string(), expected_run_output);
+        assert_eq!(job.status(), JobStatus::Stopped);
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn test_stop_between_children_stops_the_group() {
+        let stopping = SingleJob::with_context_task(95, |context: &JobContext| {
+            context.cancellation().cancel();
+            Ok::<_, String>("done".to_string())
+        });
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![
+            Box::new(stopping),
+            Box::new(SingleJob::with_task(96, || {
+                Ok::<_, String>("ok".to_string())
+            })),
+        ])));
+
+        assert_eq!(job.run().unwrap().status, JobStatus::Stopped);
+        assert_eq!(job.status(), JobStatus::Stopped);
+        assert_eq!(
+            job.outline().ids_with_status(JobStatus::Completed),
+            vec![95]
+        );
+        // The stopped group can be run again, and only the child that never ran starts.
+        assert_eq!(job.run().unwrap().children.len(), 1);
+    }
+
+    #[test]
+    fn test_pause_and_resume_without_a_task() {
+        let mut job = Job::new(Box::new(SingleJob::new(54)));
+        job.run().unwrap();
+
+        assert_eq!(job.pause().unwrap().to_string(), "Pausing single job 54");
+        assert_eq!(job.status(), JobStatus::Paused);
+        assert_eq!(job.resume().unwrap().to_string(), "Running single job 54");
+        assert_eq!(
+            job.resume().unwrap_err().to_string(),
+            "only paused jobs can be resumed, not Running"
+        );
+    }
+
+    #[test]
+    fn test_resuming_a_group_skips_completed_children() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&runs);
+        let first = SingleJob::with_task(120, move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, String>("ok".to_string())
+        });
+        let mut paused_once = false;
+        let second = SingleJob::with_context_task(121, move |context: &JobContext| {
+            if !paused_once {
+                paused_once = true;
+                context.cancellation().sleep(Duration::from_secs(30));
+                context.checkpoint()?;
+            }
+            Ok::<_, CancelReason>("resumed".to_string())
+        });
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![
+            Box::new(first),
+            Box::new(second),
+            succeeding(122),
+        ])));
+        let token = job.cancellation_token();
+        let pauser = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            token.pause();
+        });
+
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Running multiple jobs:\nSingle job 120 completed: ok\nSingle job 121 was paused"
+        );
+        assert_eq!(job.status(), JobStatus::Paused);
+        pauser.join().unwrap();
+
+        assert_eq!(
+            job.resume().unwrap().to_string(),
+            "Running multiple jobs:\nSingle job 121 completed: resumed\nSingle job 122 completed: ok"
+        );
+        assert_eq!(job.status(), JobStatus::Completed);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_nested_multiple_job_maps_to_nested_reports() {
+        let inner = Box::new(MultipleJob::new(vec![succeeding(110), failing(111)]));
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![inner, succeeding(112)])));
+
+        let report = job.run().unwrap();
+        assert_eq!(report.id, None);
+        assert_eq!(report.status, JobStatus::Failed);
+        assert_eq!(report.children.len(), 2);
+
+        let inner_report = &report.children[0];
+        assert_eq!(inner_report.status, JobStatus::Failed);
+        let ids: Vec<Option<u32>> = inner_report.children.iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![Some(110), Some(111)]);
+        assert_eq!(report.children[1].id, Some(112));
+        assert!(report.finished_at >= inner_report.finished_at);
+
+        let tree = report.render(ReportFormat::Tree);
+        let labels: Vec<&str> = tree
+            .lines()
+            .map(|line| line.split(" [").next().unwrap())
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                "- group",
+                "  - group",
+                "    - job 110",
+                "    - job 111",
+                "    note: Failure policy continue-on-error: 1 of 2 jobs failed, 0 skipped",
+                "  - job 112",
+                "  note: Failure policy continue-on-error: 1 of 2 jobs failed, 0 skipped",
+            ]
+        );
     }
 }
@@ -0,0 +1,218 @@
+use super::clock::{Clock, SystemClock};
+use std::fmt::{self, Display};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, SystemTime};
+
+// How often `CancellationToken::sleep` wakes up to look at the token.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+// What has been asked of the work holding a token, stored in `TokenState::requested`.
+const NOTHING: u8 = 0;
+const STOP: u8 = 1;
+const PAUSE: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    Stopped,
+    // The work should return early in a state it can be run again from.
+    Paused,
+    TimedOut(Duration),
+}
+
+impl Display for CancelReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CancelReason::Stopped => write!(f, "stopped"),
+            CancelReason::Paused => write!(f, "paused"),
+            CancelReason::TimedOut(timeout) => write!(f, "timed out after {:?}", timeout),
+        }
+    }
+}
+
+struct TokenState {
+    requested: AtomicU8,
+    deadline: Option<(SystemTime, Duration)>,
+    parent: Option<CancellationToken>,
+    // Deadlines and sleeps follow this clock; children share their parent's.
+    clock: Arc<dyn Clock>,
+}
+
+// A cheaply clonable flag that running work checks cooperatively. A child token is
+// cancelled whenever its parent is, which is how a stopped group reaches its children.
+#[derive(Clone)]
+pub struct CancellationToken {
+    state: Arc<TokenState>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        CancellationToken {
+            state: Arc::new(TokenState {
+                requested: AtomicU8::new(NOTHING),
+                deadline: None,
+                parent: None,
+                clock,
+            }),
+        }
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.state.clock
+    }
+
+    pub fn child(&self) -> Self {
+        self.linked(None)
+    }
+
+    // A child token that also cancels itself once `timeout` has elapsed from now.
+    pub fn child_with_timeout(&self, timeout: Duration) -> Self {
+        self.linked(Some((self.state.clock.now() + timeout, timeout)))
+    }
+
+    // A child token whose deadlines and sleeps follow `clock` instead of this token's.
+    pub fn child_with_clock(&self, clock: Arc<dyn Clock>) -> Self {
+        CancellationToken {
+            state: Arc::new(TokenState {
+                requested: AtomicU8::new(NOTHING),
+                deadline: None,
+                parent: Some(self.clone()),
+                clock,
+            }),
+        }
+    }
+
+    fn linked(&self, deadline: Option<(SystemTime, Duration)>) -> Self {
+        CancellationToken {
+            state: Arc::new(TokenState {
+                requested: AtomicU8::new(NOTHING),
+                deadline,
+                parent: Some(self.clone()),
+                clock: Arc::clone(&self.state.clock),
+            }),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.state.requested.store(STOP, Ordering::SeqCst);
+    }
+
+    // Asks the work to return early so it can be resumed later. A pending stop wins.
+    pub fn pause(&self) {
+        let _ = self.state.requested.compare_exchange(
+            NOTHING,
+            PAUSE,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+    }
+
+    // Clears an explicit cancellation or pause so the owning job can be run again.
+    pub fn reset(&self) {
+        self.state.requested.store(NOTHING, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    pub fn reason(&self) -> Option<CancelReason> {
+        match self.state.requested.load(Ordering::SeqCst) {
+            STOP => return Some(CancelReason::Stopped),
+            PAUSE => return Some(CancelReason::Paused),
+            _ => {}
+        }
+        if let Some((deadline, timeout)) = self.state.deadline
+            && self.state.clock.now() >= deadline
+        {
+            return Some(CancelReason::TimedOut(timeout));
+        }
+        self.state
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.reason())
+    }
+
+    // Sleeps for `duration` unless cancelled first; returns whether the full time elapsed.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let clock = &self.state.clock;
+        // A sleep too long to have an end on the clock lasts until cancelled.
+        let until = clock.now().checked_add(duration);
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            let remaining = match until {
+                Some(until) => until.duration_since(clock.now()).unwrap_or_default(),
+                None => POLL_INTERVAL,
+            };
+            if remaining.is_zero() {
+                return true;
+            }
+            clock.sleep(POLL_INTERVAL.min(remaining));
+        }
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_cancelling_a_parent_cancels_its_children() {
+        let parent = CancellationToken::new();
+        let child = parent.child();
+        let grandchild = child.child();
+        assert!(!grandchild.is_cancelled());
+
+        parent.cancel();
+        assert_eq!(grandchild.reason(), Some(CancelReason::Stopped));
+
+        parent.reset();
+        assert!(!grandchild.is_cancelled());
+
+        child.cancel();
+        assert!(grandchild.is_cancelled());
+        assert!(!parent.is_cancelled());
+    }
+
+    #[test]
+    fn test_pause_reaches_children_and_yields_to_stop() {
+        let parent = CancellationToken::new();
+        let child = parent.child();
+
+        parent.pause();
+        assert_eq!(child.reason(), Some(CancelReason::Paused));
+
+        parent.cancel();
+        parent.pause();
+        assert_eq!(child.reason(), Some(CancelReason::Stopped));
+
+        parent.reset();
+        assert_eq!(child.reason(), None);
+    }
+
+    #[test]
+    fn test_timeout_cancels_token_and_interrupts_sleep() {
+        let token = CancellationToken::new().child_with_timeout(Duration::from_millis(10));
+        let started = Instant::now();
+
+        assert!(!token.sleep(Duration::from_secs(5)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            token.reason(),
+            Some(CancelReason::TimedOut(Duration::from_millis(10)))
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+#[cfg(test)]
+use std::thread::ThreadId;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Where jobs and schedulers get the current time from, so tests can control it.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    // A reading for measuring how long something took. Only differences between readings
+    // mean anything; unlike `now`, the system clock's never jumps when the wall clock is set.
+    fn monotonic(&self) -> Duration {
+        self.now().duration_since(UNIX_EPOCH).unwrap_or_default()
+    }
+
+    // Lets `duration` pass on this clock.
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        ORIGIN.get_or_init(Instant::now).elapsed()
+    }
+}
+
+// A simulated clock that only moves when told to. Sleeping on it advances it instead of
+// blocking, so a job that waits for minutes finishes at once and always at the same time.
+//
+// That only holds for one sleeper at a time: threads sleeping at once would each move the
+// clock by their own sleep, and the times they see would depend on thread scheduling.
+// Jobs that sleep in parallel need the system clock; under the test harness they are
+// rejected outright.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<SystemTime>,
+    // The only thread allowed to sleep, once the harness has confined sleeping to one.
+    #[cfg(test)]
+    sleeper: Mutex<Option<ThreadId>>,
+}
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> Self {
+        ManualClock {
+            now: Mutex::new(start),
+            #[cfg(test)]
+            sleeper: Mutex::new(None),
+        }
+    }
+
+    // Makes a sleep from any other thread fail the test, rather than silently give times
+    // that vary from run to run.
+    #[cfg(test)]
+    pub fn confine_sleep_to(&self, thread: ThreadId) {
+        *self.sleeper.lock().unwrap() = Some(thread);
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        #[cfg(test)]
+        if let Some(sleeper) = *self.sleeper.lock().unwrap() {
+            assert!(
+                sleeper == thread::current().id(),
+                "a simulated clock cannot be slept on from parallel jobs; their times would depend on thread scheduling"
+            );
+        }
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_manual_clock_moves_only_when_told() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_000);
+        let clock = ManualClock::new(start);
+        assert_eq!(clock.now(), start);
+
+        let reading = clock.monotonic();
+        clock.advance(Duration::from_secs(5));
+        clock.sleep(Duration::from_millis(250));
+        assert_eq!(clock.now(), start + Duration::from_millis(5_250));
+        assert_eq!(clock.monotonic() - reading, Duration::from_millis(5_250));
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+
+    #[test]
+    fn test_confined_clock_rejects_sleeps_from_other_threads() {
+        let clock = ManualClock::new(UNIX_EPOCH);
+        clock.confine_sleep_to(thread::current().id());
+        clock.sleep(Duration::from_secs(1));
+
+        let rejected = thread::scope(|scope| {
+            scope
+                .spawn(|| clock.sleep(Duration::from_secs(1)))
+                .join()
+                .is_err()
+        });
+        assert!(rejected);
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(1));
+    }
+}
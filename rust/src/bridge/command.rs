@@ -0,0 +1,350 @@
+use super::cancel::CancelReason;
+use super::report::JobReport;
+use super::{JobContext, JobImpl, JobOutline, JobStatus};
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// How often a running command is checked for exit and for cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+const SIGKILL: i32 = 9;
+
+unsafe extern "C" {
+    // From the C library std already links. A negative `pid` signals a whole process group.
+    safe fn kill(pid: i32, signal: i32) -> i32;
+}
+
+// A child process that has been spawned but not yet reaped. The pipes are drained on
+// background threads so a chatty process cannot block on a full pipe buffer.
+struct RunningCommand {
+    child: Child,
+    stdout: JoinHandle<String>,
+    stderr: JoinHandle<String>,
+}
+
+impl RunningCommand {
+    // The process leads its own group, so this also reaches anything it started, such as
+    // the commands of a shell. Those hold the pipes open, and draining them would
+    // otherwise wait for them to finish.
+    fn terminate(&mut self) {
+        let _ = kill(-(self.child.id() as i32), SIGKILL);
+        let _ = self.child.kill();
+    }
+}
+
+// A concrete "Implementor" that runs an external process.
+pub struct CommandJob {
+    id: u32,
+    program: String,
+    args: Vec<String>,
+    status: JobStatus,
+    running: Option<RunningCommand>,
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+}
+
+impl CommandJob {
+    pub fn new(id: u32, program: impl Into<String>) -> Self {
+        CommandJob {
+            id,
+            program: program.into(),
+            args: Vec::new(),
+            status: JobStatus::Pending,
+            running: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    // Spawns the process without waiting for it, so it can be stopped while in flight.
+    pub fn start(&mut self) -> io::Result<()> {
+        self.spawn(None)
+    }
+
+    // With a context, every line the process prints also goes to the job's log as it
+    // arrives, stderr lines marked as such.
+    fn spawn(&mut self, context: Option<&JobContext>) -> io::Result<()> {
+        if self.running.is_some() {
+            return Ok(());
+        }
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()?;
+
+        let stdout = drain(child.stdout.take(), context.cloned(), "");
+        let stderr = drain(child.stderr.take(), context.cloned(), "stderr: ");
+        self.stdout.clear();
+        self.stderr.clear();
+        self.exit_code = None;
+        self.status = JobStatus::Running;
+        self.running = Some(RunningCommand {
+            child,
+            stdout,
+            stderr,
+        });
+        Ok(())
+    }
+
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    // Polls rather than blocking in `wait`, so a cancelled run can kill the process.
+    fn wait_for_exit(&mut self, context: &JobContext) -> io::Result<()> {
+        let Some(running) = self.running.as_mut() else {
+            return Ok(());
+        };
+
+        while running.child.try_wait()?.is_none() {
+            if context.is_cancelled() {
+                running.terminate();
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    fn kill(&mut self) {
+        if let Some(running) = self.running.as_mut() {
+            // The process may already have exited on its own; reaping below covers both cases.
+            running.terminate();
+            let _ = self.reap();
+        }
+    }
+
+    fn reap(&mut self) -> io::Result<Option<i32>> {
+        let Some(mut running) = self.running.take() else {
+            return Ok(self.exit_code);
+        };
+
+        let status = running.child.wait()?;
+        self.stdout = running.stdout.join().unwrap_or_default();
+        self.stderr = running.stderr.join().unwrap_or_default();
+        self.exit_code = status.code();
+        Ok(self.exit_code)
+    }
+}
+
+fn drain<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    log: Option<JobContext>,
+    prefix: &'static str,
+) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = String::new();
+        let Some(pipe) = pipe else {
+            return output;
+        };
+        let mut pipe = BufReader::new(pipe);
+        let mut line = String::new();
+        while matches!(pipe.read_line(&mut line), Ok(read) if read > 0) {
+            if let Some(context) = &log {
+                context.log(format!("{}{}", prefix, line.trim_end_matches(['\r', '\n'])));
+            }
+            output.push_str(&line);
+            line.clear();
+        }
+        output
+    })
+}
+
+impl CommandJob {
+    fn execute(&mut self, context: &JobContext) -> String {
+        if let Err(error) = self.spawn(Some(context)) {
+            self.status = JobStatus::Failed;
+            return format!("Command job {} failed to start: {}", self.id, error);
+        }
+
+        let exit = self.wait_for_exit(context).and_then(|_| self.reap());
+        match context.cancellation().reason() {
+            Some(CancelReason::Stopped) => {
+                self.status = JobStatus::Stopped;
+                return format!("Command job {} was stopped", self.id);
+            }
+            // The process is killed like on stop, so resuming runs the command again.
+            Some(CancelReason::Paused) => {
+                self.status = JobStatus::Paused;
+                return format!("Command job {} was paused", self.id);
+            }
+            Some(CancelReason::TimedOut(timeout)) => {
+                self.status = JobStatus::Failed;
+                return format!("Command job {} timed out after {:?}", self.id, timeout);
+            }
+            None => {}
+        }
+
+        match exit {
+            Ok(Some(0)) => {
+                self.status = JobStatus::Completed;
+                format!(
+                    "Command job {} completed: {}",
+                    self.id,
+                    self.stdout.trim_end()
+                )
+            }
+            Ok(Some(code)) => {
+                self.status = JobStatus::Failed;
+                format!(
+                    "Command job {} failed with exit code {}: {}",
+                    self.id,
+                    code,
+                    self.stderr.trim_end()
+                )
+            }
+            Ok(None) => {
+                self.status = JobStatus::Failed;
+                format!("Command job {} was terminated by a signal", self.id)
+            }
+            Err(error) => {
+                self.status = JobStatus::Failed;
+                format!("Command job {} failed: {}", self.id, error)
+            }
+        }
+    }
+}
+
+impl JobImpl for CommandJob {
+    fn run(&mut self, context: &JobContext) -> JobReport {
+        let started_at = context.now();
+        context.record(JobStatus::Running);
+        let output = self.execute(context);
+        context.record(self.status);
+        JobReport::new(Some(self.id), self.status, output).with_span(started_at, context.now())
+    }
+
+    fn stop(&mut self) -> JobReport {
+        self.kill();
+        self.status = JobStatus::Stopped;
+        JobReport::new(
+            Some(self.id),
+            self.status,
+            format!("Stopping command job {}", self.id),
+        )
+    }
+
+    // There is no portable way to suspend a process, so a paused command is killed and
+    // started from scratch when resumed.
+    fn pause(&mut self) -> JobReport {
+        if self.running.is_some() {
+            self.kill();
+            self.status = JobStatus::Paused;
+        }
+        JobReport::new(
+            Some(self.id),
+            self.status,
+            format!("Pausing command job {}", self.id),
+        )
+    }
+
+    fn status(&self) -> JobStatus {
+        self.status
+    }
+
+    fn outline(&self) -> JobOutline {
+        JobOutline::leaf(self.id, self.status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::Job;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_command_job_completes() {
+        let mut job = Job::new(Box::new(CommandJob::new(1, "echo").arg("hello")));
+        assert_eq!(job.status(), JobStatus::Pending);
+
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Command job 1 completed: hello"
+        );
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_command_job_captures_output_and_exit_code() {
+        let mut command_job =
+            CommandJob::new(2, "sh").args(["-c", "echo out; echo err >&2; exit 3"]);
+
+        assert_eq!(
+            command_job.run(&JobContext::default()).to_string(),
+            "Command job 2 failed with exit code 3: err"
+        );
+        assert_eq!(command_job.status(), JobStatus::Failed);
+        assert_eq!(command_job.stdout(), "out\n");
+        assert_eq!(command_job.stderr(), "err\n");
+        assert_eq!(command_job.exit_code(), Some(3));
+    }
+
+    #[test]
+    fn test_command_job_missing_program_fails() {
+        let mut command_job = CommandJob::new(3, "definitely-not-a-real-program");
+        assert!(
+            command_job
+                .run(&JobContext::default())
+                .output
+                .starts_with("Command job 3 failed to start:")
+        );
+        assert_eq!(command_job.status(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_command_job_stop_terminates_process() {
+        let mut command_job = CommandJob::new(4, "sleep").arg("30");
+        command_job.start().unwrap();
+        assert_eq!(command_job.status(), JobStatus::Running);
+
+        let started = Instant::now();
+        assert_eq!(command_job.stop().to_string(), "Stopping command job 4");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(command_job.status(), JobStatus::Stopped);
+        assert_eq!(command_job.exit_code(), None);
+    }
+
+    #[test]
+    fn test_command_job_stop_terminates_processes_the_command_started() {
+        let mut command_job = CommandJob::new(5, "sh").args(["-c", "sleep 30; echo x"]);
+        command_job.start().unwrap();
+
+        let started = Instant::now();
+        command_job.stop();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(command_job.status(), JobStatus::Stopped);
+        assert_eq!(command_job.stdout(), "");
+    }
+}
@@ -0,0 +1,302 @@
+use super::manager::JobManager;
+use super::report::ReportFormat;
+use super::{JobError, JobStatus};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+// The control protocol is one request per line:
+//
+//   list | status ID | report ID [plain|tree|json] | stop ID | pause ID | resume ID
+//
+// answered by either `ok N` followed by N lines of output, or `error MESSAGE`.
+fn handle(manager: &Arc<JobManager>, request: &str) -> Result<Vec<String>, String> {
+    let words: Vec<&str> = request.split_whitespace().collect();
+    let (command, arguments) = words.split_first().ok_or("empty request")?;
+    let id = || -> Result<u32, String> {
+        let id = arguments.first().ok_or("missing job id")?;
+        id.parse()
+            .map_err(|_| format!("expected a job id, not '{}'", id))
+    };
+    let failed = |error: JobError| error.to_string();
+
+    match *command {
+        "list" => Ok(manager
+            .ids()
+            .into_iter()
+            .filter_map(|id| Some(format!("{} {:?}", id, manager.status(id)?)))
+            .collect()),
+        "status" => {
+            let id = id()?;
+            let status = manager.status(id).ok_or(JobError::UnknownJob(id));
+            Ok(vec![format!("{:?}", status.map_err(failed)?)])
+        }
+        "report" => {
+            let id = id()?;
+            let format = match arguments.get(1).copied() {
+                None | Some("plain") => ReportFormat::Plain,
+                Some("tree") => ReportFormat::Tree,
+                Some("json") => ReportFormat::Json,
+                Some(other) => return Err(format!("unknown report format '{}'", other)),
+            };
+            manager.status(id).ok_or(failed(JobError::UnknownJob(id)))?;
+            let report = manager
+                .report(id)
+                .ok_or(format!("job {} has not run yet", id))?;
+            Ok(report.render(format).lines().map(str::to_string).collect())
+        }
+        "stop" => manager.stop(id()?).map(|_| Vec::new()).map_err(failed),
+        "pause" => manager.pause(id()?).map(|_| Vec::new()).map_err(failed),
+        // Resuming runs the job until it finishes, so it happens in the background and
+        // the request only checks that it can start.
+        "resume" => {
+            let id = id()?;
+            match manager.status(id) {
+                None => Err(failed(JobError::UnknownJob(id))),
+                Some(JobStatus::Paused) => {
+                    let manager = Arc::clone(manager);
+                    thread::spawn(move || manager.resume(id));
+                    Ok(Vec::new())
+                }
+                Some(status) => Err(failed(JobError::NotPaused(status))),
+            }
+        }
+        other => Err(format!("unknown command '{}'", other)),
+    }
+}
+
+fn serve_connection(stream: UnixStream, manager: &Arc<JobManager>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for request in BufReader::new(stream).lines() {
+        match handle(manager, &request?) {
+            Ok(lines) => {
+                writeln!(writer, "ok {}", lines.len())?;
+                for line in lines {
+                    writeln!(writer, "{}", line)?;
+                }
+            }
+            Err(message) => writeln!(writer, "error {}", message)?,
+        }
+    }
+    Ok(())
+}
+
+// Serves the jobs of a manager on a Unix domain socket. Each client connection gets its
+// own thread. Dropping the server stops accepting and removes the socket file.
+pub struct ControlServer {
+    path: PathBuf,
+    closing: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    // Refuses a socket another server still answers on, but replaces a stale one. Any
+    // other file at `path` is left alone.
+    pub fn start(path: impl AsRef<Path>, manager: Arc<JobManager>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} is already being served", path.display()),
+            ));
+        }
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(&path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            Err(_) => {}
+        }
+        let listener = UnixListener::bind(&path)?;
+
+        let closing = Arc::new(AtomicBool::new(false));
+        let acceptor = {
+            let closing = Arc::clone(&closing);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if closing.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    let manager = Arc::clone(&manager);
+                    thread::spawn(move || serve_connection(stream, &manager));
+                }
+            })
+        };
+        Ok(ControlServer {
+            path,
+            closing,
+            acceptor: Some(acceptor),
+        })
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.closing.store(true, Ordering::SeqCst);
+        // Wakes the acceptor, which is blocked until the next connection.
+        let _ = UnixStream::connect(&self.path);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Sends one request to the server at `path`. The outer error is a connection or
+// protocol problem; the inner one is the server's answer to a bad request.
+pub fn send_request(
+    path: impl AsRef<Path>,
+    request: &str,
+) -> io::Result<Result<Vec<String>, String>> {
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{}", request)?;
+    let mut lines = BufReader::new(stream).lines();
+    let malformed = |line: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected response '{}'", line),
+        )
+    };
+
+    let header = lines.next().ok_or_else(|| malformed(""))??;
+    if let Some(message) = header.strip_prefix("error ") {
+        return Ok(Err(message.to_string()));
+    }
+    let count: usize = header
+        .strip_prefix("ok ")
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(|| malformed(&header))?;
+    let body: Vec<String> = lines.take(count).collect::<io::Result<_>>()?;
+    if body.len() < count {
+        return Err(malformed("a truncated body"));
+    }
+    Ok(Ok(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{Job, JobContext, SingleJob};
+    use std::time::{Duration, Instant};
+
+    fn socket(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("control-{}-{}.sock", name, std::process::id()))
+    }
+
+    fn wait_for(manager: &JobManager, id: u32, status: JobStatus) {
+        let started = Instant::now();
+        while manager.status(id) != Some(status) {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_list_status_and_report() {
+        let manager = Arc::new(JobManager::new());
+        let done = Job::new(Box::new(SingleJob::with_task(1, || {
+            Ok::<_, String>("ok".to_string())
+        })));
+        let id = manager.register(done).unwrap();
+        manager.run(id).unwrap();
+        manager
+            .register(Job::new(Box::new(SingleJob::new(2))))
+            .unwrap();
+
+        let path = socket("query");
+        let _server = ControlServer::start(&path, Arc::clone(&manager)).unwrap();
+        let request = |line: &str| send_request(&path, line).unwrap();
+        assert_eq!(
+            request("list"),
+            Ok(vec!["1 Completed".to_string(), "2 Pending".to_string()])
+        );
+        assert_eq!(request("status 2"), Ok(vec!["Pending".to_string()]));
+        assert_eq!(
+            request("report 1"),
+            Ok(vec!["Single job 1 completed: ok".to_string()])
+        );
+        assert_eq!(
+            request("report 2"),
+            Err("job 2 has not run yet".to_string())
+        );
+        assert_eq!(request("status 9"), Err("no job with id 9".to_string()));
+        assert_eq!(
+            request("launch 1"),
+            Err("unknown command 'launch'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_pause_and_resume_a_running_job() {
+        let manager = Arc::new(JobManager::new());
+        let mut first_run = true;
+        let waiting = Job::new(Box::new(SingleJob::with_context_task(
+            1,
+            move |context: &JobContext| {
+                if std::mem::take(&mut first_run) {
+                    context.cancellation().sleep(Duration::from_secs(30));
+                }
+                context.checkpoint().map_err(|reason| reason.to_string())?;
+                Ok::<_, String>("finished".to_string())
+            },
+        )));
+        let id = manager.register(waiting).unwrap();
+        let path = socket("pause");
+        let _server = ControlServer::start(&path, Arc::clone(&manager)).unwrap();
+
+        let worker = {
+            let manager = Arc::clone(&manager);
+            thread::spawn(move || manager.run(id))
+        };
+        wait_for(&manager, id, JobStatus::Running);
+        assert_eq!(
+            send_request(&path, "resume 1").unwrap(),
+            Err("only paused jobs can be resumed, not Running".to_string())
+        );
+        assert_eq!(send_request(&path, "pause 1").unwrap(), Ok(Vec::new()));
+        worker.join().unwrap().unwrap();
+        wait_for(&manager, id, JobStatus::Paused);
+
+        assert_eq!(send_request(&path, "resume 1").unwrap(), Ok(Vec::new()));
+        wait_for(&manager, id, JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_server_refuses_a_live_socket_and_cleans_up() {
+        let manager = Arc::new(JobManager::new());
+        let path = socket("cleanup");
+        let server = ControlServer::start(&path, Arc::clone(&manager)).unwrap();
+        let error = ControlServer::start(&path, Arc::clone(&manager))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+
+        drop(server);
+        assert!(!path.exists());
+        assert!(send_request(&path, "list").is_err());
+    }
+
+    #[test]
+    fn test_server_leaves_a_regular_file_in_place() {
+        let path = socket("regular");
+        fs::write(&path, "notes").unwrap();
+        let error = ControlServer::start(&path, Arc::new(JobManager::new()))
+            .err()
+            .unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "notes");
+        fs::remove_file(&path).unwrap();
+    }
+}
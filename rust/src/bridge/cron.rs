@@ -0,0 +1,567 @@
+use super::cancel::CancellationToken;
+use super::clock::{Clock, SystemClock};
+use super::progress::JobEvent;
+use super::report::JobReport;
+use super::{Job, JobError, JobStatus};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// Occurrences found to be more than this far in the past are treated as missed.
+const MISSED_AFTER: Duration = Duration::from_secs(60);
+
+// Upper bound on how many missed occurrences one check will catch up on.
+const MAX_CATCH_UP: usize = 1000;
+
+// How many of the latest occurrences the history keeps.
+const RECENT_RUNS: usize = 1000;
+
+// The values one cron field allows, as a bit set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CronField {
+    allowed: u64,
+    // Whether the field was anything other than `*` or `*/N`, as in Vixie cron; matters for
+    // the day fields.
+    restricted: bool,
+}
+
+impl CronField {
+    fn parse(text: &str, name: &str, min: u32, max: u32) -> Result<Self, JobError> {
+        let invalid = |reason: &str| {
+            JobError::InvalidSchedule(format!("{} field '{}': {}", name, text, reason))
+        };
+
+        let mut allowed = 0u64;
+        for part in text.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step.parse().map_err(|_| invalid("bad step"))?;
+                    if step == 0 {
+                        return Err(invalid("step must be positive"));
+                    }
+                    (range, step)
+                }
+                None => (part, 1),
+            };
+
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                let start = start.parse().map_err(|_| invalid("bad range start"))?;
+                let end = end.parse().map_err(|_| invalid("bad range end"))?;
+                (start, end)
+            } else {
+                let value = range.parse().map_err(|_| invalid("bad value"))?;
+                // `5/15` means "from 5 to the end, every 15", as in other crons.
+                (value, if step > 1 { max } else { value })
+            };
+
+            if start < min || end > max || start > end {
+                return Err(invalid(&format!("values must be within {}-{}", min, max)));
+            }
+            for value in (start..=end).step_by(step as usize) {
+                allowed |= 1 << value;
+            }
+        }
+
+        Ok(CronField {
+            allowed,
+            restricted: !text.starts_with('*'),
+        })
+    }
+
+    fn contains(self, value: u64) -> bool {
+        self.allowed & (1 << value) != 0
+    }
+}
+
+// A five-field cron expression: minute, hour, day of month, month and day of week,
+// evaluated in UTC. Day of week runs from 0 (Sunday) to 7 (Sunday again).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: CronField,
+    hours: CronField,
+    days_of_month: CronField,
+    months: CronField,
+    days_of_week: CronField,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, JobError> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields.as_slice() else {
+            return Err(JobError::InvalidSchedule(format!(
+                "expected 5 fields, found {} in '{}'",
+                fields.len(),
+                expression
+            )));
+        };
+
+        let mut days_of_week = CronField::parse(days_of_week, "day-of-week", 0, 7)?;
+        if days_of_week.contains(7) {
+            days_of_week.allowed |= 1;
+        }
+        Ok(CronSchedule {
+            minutes: CronField::parse(minutes, "minute", 0, 59)?,
+            hours: CronField::parse(hours, "hour", 0, 23)?,
+            days_of_month: CronField::parse(days_of_month, "day-of-month", 1, 31)?,
+            months: CronField::parse(months, "month", 1, 12)?,
+            days_of_week,
+        })
+    }
+
+    // The first matching minute strictly after `after`, or `None` if nothing matches in
+    // the next few years (for example, February 30th).
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let seconds = after.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let mut time = (seconds / 60 + 1) * 60;
+        let (start_year, _, _) = civil_from_days(time / SECONDS_PER_DAY);
+
+        loop {
+            let days = time / SECONDS_PER_DAY;
+            let (year, month, day) = civil_from_days(days);
+            if year > start_year + 5 {
+                return None;
+            }
+
+            if !self.months.contains(month) {
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                time = days_from_civil(year, month, 1) * SECONDS_PER_DAY;
+                continue;
+            }
+            // 1970-01-01 was a Thursday.
+            let weekday = (days + 4) % 7;
+            if !self.day_matches(day, weekday) {
+                time = (days + 1) * SECONDS_PER_DAY;
+                continue;
+            }
+            if !self.hours.contains(time % SECONDS_PER_DAY / 3600) {
+                time = (time / 3600 + 1) * 3600;
+                continue;
+            }
+            if !self.minutes.contains(time % 3600 / 60) {
+                time += 60;
+                continue;
+            }
+            return Some(UNIX_EPOCH + Duration::from_secs(time));
+        }
+    }
+
+    // When both day fields are restricted a day matching either one fires, as in cron.
+    fn day_matches(&self, day: u64, weekday: u64) -> bool {
+        let by_month = self.days_of_month.contains(day);
+        let by_week = self.days_of_week.contains(weekday);
+        if self.days_of_month.restricted && self.days_of_week.restricted {
+            by_month || by_week
+        } else {
+            by_month && by_week
+        }
+    }
+}
+
+// Converts days since the epoch to a (year, month, day) date in the proleptic Gregorian
+// calendar, following Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// What to do with occurrences that passed while the scheduler was not checking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissedRunPolicy {
+    // Only run an occurrence that is due right now.
+    Skip,
+    // Run once for the most recent occurrence, however many were missed.
+    RunOnce,
+    // Run every missed occurrence, oldest first.
+    CatchUp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome {
+    Skipped,
+    Finished(JobReport),
+    Rejected(JobError),
+}
+
+// One occurrence of a schedule and what happened to it. Every triggered run gets a
+// fresh job, so its statuses are its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledRun {
+    pub scheduled_for: SystemTime,
+    pub started_at: Option<SystemTime>,
+    pub statuses: Vec<JobStatus>,
+    pub outcome: RunOutcome,
+}
+
+pub struct CronScheduler {
+    schedule: CronSchedule,
+    policy: MissedRunPolicy,
+    clock: Arc<dyn Clock>,
+    factory: Box<dyn FnMut() -> Job + Send>,
+    last_checked: SystemTime,
+    history: VecDeque<ScheduledRun>,
+}
+
+impl CronScheduler {
+    pub fn new<F>(schedule: CronSchedule, factory: F) -> Self
+    where
+        F: FnMut() -> Job + Send + 'static,
+    {
+        CronScheduler {
+            schedule,
+            policy: MissedRunPolicy::RunOnce,
+            clock: Arc::new(SystemClock),
+            factory: Box::new(factory),
+            last_checked: SystemTime::now(),
+            history: VecDeque::new(),
+        }
+    }
+
+    // Occurrences are counted from the clock's current time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.last_checked = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_missed_run_policy(mut self, policy: MissedRunPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn next_run(&self) -> Option<SystemTime> {
+        self.schedule.next_after(self.last_checked)
+    }
+
+    // The latest occurrences, oldest first.
+    pub fn history(&self) -> &VecDeque<ScheduledRun> {
+        &self.history
+    }
+
+    fn remember(&mut self, run: ScheduledRun) {
+        if self.history.len() == RECENT_RUNS {
+            self.history.pop_front();
+        }
+        self.history.push_back(run);
+    }
+
+    // Handles every occurrence that came due since the last check and returns how many
+    // runs were started.
+    pub fn run_due(&mut self) -> usize {
+        self.run_due_under(&CancellationToken::with_clock(Arc::clone(&self.clock)))
+    }
+
+    // Runs are cancelled along with `token`.
+    fn run_due_under(&mut self, token: &CancellationToken) -> usize {
+        let now = self.clock.now();
+        let mut due = Vec::new();
+        let mut cursor = self.last_checked;
+        while let Some(next) = self.schedule.next_after(cursor) {
+            if next > now || due.len() == MAX_CATCH_UP {
+                break;
+            }
+            due.push(next);
+            cursor = next;
+        }
+        self.last_checked = now;
+
+        let Some(latest) = due.last().copied() else {
+            return 0;
+        };
+        let latest_on_time = now.duration_since(latest).unwrap_or_default() < MISSED_AFTER;
+
+        let mut started = 0;
+        for scheduled_for in due {
+            let run = match self.policy {
+                MissedRunPolicy::Skip => scheduled_for == latest && latest_on_time,
+                MissedRunPolicy::RunOnce => scheduled_for == latest,
+                MissedRunPolicy::CatchUp => true,
+            };
+            if run {
+                self.trigger(scheduled_for, token);
+                started += 1;
+            } else {
+                self.remember(ScheduledRun {
+                    scheduled_for,
+                    started_at: None,
+                    statuses: Vec::new(),
+                    outcome: RunOutcome::Skipped,
+                });
+            }
+        }
+        started
+    }
+
+    // Sleeps on the scheduler's clock until each occurrence and runs it, until `token` is
+    // cancelled.
+    pub fn run_until_cancelled(&mut self, token: &CancellationToken) {
+        let token = token.child_with_clock(Arc::clone(&self.clock));
+        while let Some(next) = self.next_run() {
+            let wait = next.duration_since(self.clock.now()).unwrap_or_default();
+            if !token.sleep(wait) {
+                return;
+            }
+            self.run_due_under(&token);
+        }
+    }
+
+    // Each run happens on the scheduler's clock, under a child of `token`.
+    fn trigger(&mut self, scheduled_for: SystemTime, token: &CancellationToken) {
+        let mut job = (self.factory)().with_cancellation(token.child());
+        let statuses = Arc::new(Mutex::new(vec![job.status()]));
+        let recorded = Arc::clone(&statuses);
+        job.subscribers().subscribe(move |event| {
+            if let JobEvent::StatusChanged { path, to, .. } = event
+                && path.is_empty()
+            {
+                recorded.lock().unwrap().push(*to);
+            }
+        });
+        let started_at = self.clock.now();
+        let outcome = match job.run() {
+            Ok(report) => RunOutcome::Finished(report),
+            Err(error) => RunOutcome::Rejected(error),
+        };
+        let statuses = statuses.lock().unwrap().clone();
+        self.remember(ScheduledRun {
+            scheduled_for,
+            started_at: Some(started_at),
+            statuses,
+            outcome,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::clock::ManualClock;
+    use crate::bridge::{JobContext, SingleJob};
+
+    // 2024-03-01 was a Friday.
+    fn at(day: u64, hour: u64, minute: u64) -> SystemTime {
+        let days = days_from_civil(2024, 3, day);
+        UNIX_EPOCH + Duration::from_secs(days * SECONDS_PER_DAY + hour * 3600 + minute * 60)
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_expressions() {
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        let error = CronSchedule::parse("* 5-1 * * *").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid schedule: hour field '5-1': values must be within 0-23"
+        );
+    }
+
+    #[test]
+    fn test_next_after_finds_matching_minutes() {
+        let schedule = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert_eq!(schedule.next_after(at(1, 9, 7)), Some(at(1, 9, 15)));
+        assert_eq!(schedule.next_after(at(1, 17, 45)), Some(at(4, 9, 0)));
+
+        let sundays = CronSchedule::parse("30 6 * * 7").unwrap();
+        assert_eq!(sundays.next_after(at(1, 0, 0)), Some(at(3, 6, 30)));
+
+        // Either day field may match when both are restricted.
+        let either = CronSchedule::parse("0 0 15 * 6").unwrap();
+        assert_eq!(either.next_after(at(1, 0, 0)), Some(at(2, 0, 0)));
+        // A stepped `*` counts as unrestricted, so the other day field alone decides.
+        let mondays = CronSchedule::parse("0 0 */1 * 1").unwrap();
+        assert_eq!(mondays.next_after(at(1, 0, 0)), Some(at(4, 0, 0)));
+        let first_on_a_weekend = CronSchedule::parse("0 0 1 * */6").unwrap();
+        let next = first_on_a_weekend.next_after(at(1, 0, 0)).unwrap();
+        let days = next.duration_since(UNIX_EPOCH).unwrap().as_secs() / SECONDS_PER_DAY;
+        assert_eq!(civil_from_days(days), (2024, 6, 1));
+
+        let leap_day = CronSchedule::parse("0 12 29 2 *").unwrap();
+        let next = leap_day.next_after(at(1, 0, 0)).unwrap();
+        let days = next.duration_since(UNIX_EPOCH).unwrap().as_secs() / SECONDS_PER_DAY;
+        assert_eq!(civil_from_days(days), (2028, 2, 29));
+
+        let never = CronSchedule::parse("0 0 30 2 *").unwrap();
+        assert_eq!(never.next_after(at(1, 0, 0)), None);
+    }
+
+    fn scheduler(clock: &Arc<ManualClock>, policy: MissedRunPolicy) -> CronScheduler {
+        let mut runs = 0;
+        let schedule = CronSchedule::parse("0 * * * *").unwrap();
+        CronScheduler::new(schedule, move || {
+            runs += 1;
+            let run = runs;
+            Job::new(Box::new(SingleJob::with_task(run, move || {
+                if run % 2 == 0 {
+                    Err("even run")
+                } else {
+                    Ok("odd run".to_string())
+                }
+            })))
+        })
+        .with_clock(Arc::clone(clock) as Arc<dyn Clock>)
+        .with_missed_run_policy(policy)
+    }
+
+    #[test]
+    fn test_each_run_keeps_its_own_status_history() {
+        let clock = Arc::new(ManualClock::new(at(1, 8, 30)));
+        let mut scheduler = scheduler(&clock, MissedRunPolicy::Skip);
+        assert_eq!(scheduler.next_run(), Some(at(1, 9, 0)));
+        assert_eq!(scheduler.run_due(), 0);
+
+        clock.set(at(1, 9, 0));
+        assert_eq!(scheduler.run_due(), 1);
+        clock.set(at(1, 10, 0));
+        assert_eq!(scheduler.run_due(), 1);
+
+        let statuses: Vec<&[JobStatus]> = scheduler
+            .history()
+            .iter()
+            .map(|run| run.statuses.as_slice())
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                &[JobStatus::Pending, JobStatus::Running, JobStatus::Completed][..],
+                &[JobStatus::Pending, JobStatus::Running, JobStatus::Failed][..],
+            ]
+        );
+        assert_eq!(scheduler.history()[1].scheduled_for, at(1, 10, 0));
+
+        // The history is what the job went through, not what a run usually looks like.
+        let mut unfinished = CronScheduler::new(CronSchedule::parse("0 * * * *").unwrap(), || {
+            Job::new(Box::new(SingleJob::new(9)))
+        })
+        .with_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+        clock.set(at(1, 11, 0));
+        unfinished.run_due();
+        assert_eq!(
+            unfinished.history()[0].statuses,
+            vec![JobStatus::Pending, JobStatus::Running]
+        );
+        let RunOutcome::Finished(report) = &scheduler.history()[1].outcome else {
+            panic!("the second run should have finished");
+        };
+        assert_eq!(report.started_at, at(1, 10, 0));
+    }
+
+    #[test]
+    fn test_missed_run_policies() {
+        let outcomes = |policy| {
+            let clock = Arc::new(ManualClock::new(at(1, 8, 30)));
+            let mut scheduler = scheduler(&clock, policy);
+            // Three occurrences (9:00, 10:00, 11:00) pass before the next check.
+            clock.set(at(1, 11, 20));
+            let started = scheduler.run_due();
+            let skipped: Vec<bool> = scheduler
+                .history()
+                .iter()
+                .map(|run| run.outcome == RunOutcome::Skipped)
+                .collect();
+            (started, skipped)
+        };
+
+        assert_eq!(outcomes(MissedRunPolicy::Skip), (0, vec![true, true, true]));
+        assert_eq!(
+            outcomes(MissedRunPolicy::RunOnce),
+            (1, vec![true, true, false])
+        );
+        assert_eq!(
+            outcomes(MissedRunPolicy::CatchUp),
+            (3, vec![false, false, false])
+        );
+
+        // A long outage leaves only the latest occurrences in the history.
+        let clock = Arc::new(ManualClock::new(at(1, 8, 30)));
+        let mut scheduler = scheduler(&clock, MissedRunPolicy::Skip);
+        clock.advance(Duration::from_secs(3600 * 900));
+        scheduler.run_due();
+        clock.advance(Duration::from_secs(3600 * 200));
+        scheduler.run_due();
+        assert_eq!(scheduler.history().len(), RECENT_RUNS);
+        assert_eq!(
+            scheduler.history()[0].scheduled_for,
+            at(1, 9, 0) + Duration::from_secs(3600 * 100)
+        );
+    }
+
+    #[test]
+    fn test_run_until_cancelled_sleeps_on_the_scheduler_clock() {
+        let clock = Arc::new(ManualClock::new(at(1, 8, 30)));
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let mut runs = 0;
+        let schedule = CronSchedule::parse("0 * * * *").unwrap();
+        let mut scheduler = CronScheduler::new(schedule, move || {
+            runs += 1;
+            let (run, canceller) = (runs, canceller.clone());
+            Job::new(Box::new(SingleJob::with_context_task(
+                run,
+                move |context: &JobContext| {
+                    // The third run is still busy when the scheduler is cancelled.
+                    if run == 3 {
+                        canceller.cancel();
+                        context.cancellation().sleep(Duration::from_secs(3600));
+                    }
+                    context.checkpoint().map_err(|reason| reason.to_string())?;
+                    Ok::<_, String>("ok".to_string())
+                },
+            )))
+        })
+        .with_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+
+        // Hours pass on the simulated clock without any real waiting.
+        scheduler.run_until_cancelled(&token);
+        let runs: Vec<(Option<SystemTime>, Option<JobStatus>)> = scheduler
+            .history()
+            .iter()
+            .map(|run| {
+                let status = match &run.outcome {
+                    RunOutcome::Finished(report) => Some(report.status),
+                    _ => None,
+                };
+                (run.started_at, status)
+            })
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                (Some(at(1, 9, 0)), Some(JobStatus::Completed)),
+                (Some(at(1, 10, 0)), Some(JobStatus::Completed)),
+                (Some(at(1, 11, 0)), Some(JobStatus::Stopped)),
+            ]
+        );
+        assert_eq!(clock.now(), at(1, 11, 0));
+    }
+}
@@ -0,0 +1,526 @@
+use super::cancel::CancelReason;
+use super::report::JobReport;
+use super::{
+    GroupKind, JobContext, JobError, JobImpl, JobOutline, JobStatus, StatusRules,
+    compensation_group,
+};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+struct DagNode {
+    id: u32,
+    job: Box<dyn JobImpl>,
+    depends_on: Vec<u32>,
+}
+
+// A concrete "Implementor" that runs its jobs in dependency order. A job starts as soon
+// as every job it depends on has completed; jobs downstream of a failure are skipped.
+pub struct DagJob {
+    nodes: Vec<DagNode>,
+    status: JobStatus,
+    max_concurrency: usize,
+    skipped: Vec<u32>,
+}
+
+impl DagJob {
+    pub fn new() -> Self {
+        DagJob {
+            nodes: Vec::new(),
+            status: JobStatus::Pending,
+            max_concurrency: 1,
+            skipped: Vec::new(),
+        }
+    }
+
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    // Dependencies may name jobs that are added later; `schedule` checks the whole graph.
+    // A job with an id of its own must be added under that id; groups take any free one.
+    pub fn add(
+        &mut self,
+        id: u32,
+        job: Box<dyn JobImpl>,
+        depends_on: &[u32],
+    ) -> Result<(), JobError> {
+        if let Some(own) = job.outline().id
+            && own != id
+        {
+            return Err(JobError::MismatchedId { key: id, job: own });
+        }
+        if self.nodes.iter().any(|node| node.id == id) {
+            return Err(JobError::DuplicateJob(id));
+        }
+        self.nodes.push(DagNode {
+            id,
+            job,
+            depends_on: depends_on.to_vec(),
+        });
+        Ok(())
+    }
+
+    // Returns the job ids in the order a single worker would run them.
+    pub fn schedule(&self) -> Result<Vec<u32>, JobError> {
+        let order = self.topological_order()?;
+        Ok(order
+            .into_iter()
+            .map(|index| self.nodes[index].id)
+            .collect())
+    }
+
+    pub fn skipped(&self) -> &[u32] {
+        &self.skipped
+    }
+
+    fn dependency_indices(&self) -> Result<Vec<Vec<usize>>, JobError> {
+        let positions: HashMap<u32, usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.id, index))
+            .collect();
+
+        self.nodes
+            .iter()
+            .map(|node| {
+                node.depends_on
+                    .iter()
+                    .map(|dependency| {
+                        positions
+                            .get(dependency)
+                            .copied()
+                            .ok_or(JobError::UnknownDependency {
+                                job: node.id,
+                                dependency: *dependency,
+                            })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Kahn's algorithm, breaking ties by insertion order so the result is deterministic.
+    fn topological_order(&self) -> Result<Vec<usize>, JobError> {
+        let dependencies = self.dependency_indices()?;
+        let mut remaining: Vec<usize> = dependencies.iter().map(Vec::len).collect();
+        let dependents = dependents_of(&dependencies);
+
+        let mut ready: VecDeque<usize> = (0..self.nodes.len())
+            .filter(|index| remaining[*index] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(index) = ready.pop_front() {
+            order.push(index);
+            for dependent in &dependents[index] {
+                remaining[*dependent] -= 1;
+                if remaining[*dependent] == 0 {
+                    ready.push_back(*dependent);
+                }
+            }
+        }
+
+        if order.len() == self.nodes.len() {
+            Ok(order)
+        } else {
+            Err(JobError::DependencyCycle(
+                self.find_cycle(&dependencies, &remaining),
+            ))
+        }
+    }
+
+    // Every node left with unmet dependencies after Kahn's algorithm depends on another
+    // such node, so walking dependencies from any of them must eventually revisit one.
+    fn find_cycle(&self, dependencies: &[Vec<usize>], remaining: &[usize]) -> Vec<u32> {
+        let Some(start) = (0..self.nodes.len()).find(|index| remaining[*index] > 0) else {
+            return Vec::new();
+        };
+
+        let mut path = vec![start];
+        loop {
+            let current = *path.last().unwrap();
+            let next = dependencies[current]
+                .iter()
+                .copied()
+                .find(|dependency| remaining[*dependency] > 0)
+                .unwrap();
+            if let Some(position) = path.iter().position(|index| *index == next) {
+                let mut cycle: Vec<u32> = path[position..]
+                    .iter()
+                    .map(|index| self.nodes[*index].id)
+                    .collect();
+                cycle.push(self.nodes[next].id);
+                return cycle;
+            }
+            path.push(next);
+        }
+    }
+
+    // Returns one report per node in schedule order, including skipped nodes.
+    fn run_graph(&mut self, order: &[usize], context: &JobContext) -> Vec<JobReport> {
+        let dependencies = self.dependency_indices().unwrap_or_default();
+        let dependents = dependents_of(&dependencies);
+        let count = self.nodes.len();
+        let workers = self.max_concurrency.min(count);
+        // Jobs completed by an earlier run, such as one that was paused, are not repeated.
+        let done: Vec<bool> = self
+            .nodes
+            .iter()
+            .map(|node| node.job.status() == JobStatus::Completed)
+            .collect();
+
+        let shared = Mutex::new(Scheduler {
+            remaining: dependencies
+                .iter()
+                .map(|node_dependencies| {
+                    node_dependencies
+                        .iter()
+                        .filter(|dependency| !done[**dependency])
+                        .count()
+                })
+                .collect(),
+            ready: order
+                .iter()
+                .copied()
+                .filter(|index| !done[*index])
+                .filter(|index| {
+                    dependencies[*index]
+                        .iter()
+                        .all(|dependency| done[*dependency])
+                })
+                .collect(),
+            slots: self
+                .nodes
+                .iter_mut()
+                .map(|node| Some(&mut node.job))
+                .collect(),
+            reports: vec![None; count],
+            in_flight: 0,
+        });
+        let wakeup = Condvar::new();
+
+        let work = || {
+            loop {
+                let mut scheduler = shared.lock().unwrap();
+                let next = loop {
+                    // Once cancelled, nothing new starts; jobs already running finish on
+                    // their own because they share the same context.
+                    if context.is_cancelled() {
+                        break None;
+                    }
+                    if let Some(index) = scheduler.ready.pop_front() {
+                        break Some(index);
+                    }
+                    if scheduler.in_flight == 0 {
+                        break None;
+                    }
+                    scheduler = wakeup.wait(scheduler).unwrap();
+                };
+                let Some(index) = next else {
+                    wakeup.notify_all();
+                    return;
+                };
+                let job = scheduler.slots[index].take().unwrap();
+                scheduler.in_flight += 1;
+                drop(scheduler);
+
+                let report = job.run(&context.child(index));
+                let completed = job.status() == JobStatus::Completed;
+
+                let mut scheduler = shared.lock().unwrap();
+                scheduler.in_flight -= 1;
+                scheduler.reports[index] = Some(report);
+                if completed {
+                    for dependent in &dependents[index] {
+                        scheduler.remaining[*dependent] -= 1;
+                        if scheduler.remaining[*dependent] == 0 {
+                            scheduler.ready.push_back(*dependent);
+                        }
+                    }
+                }
+                wakeup.notify_all();
+            }
+        };
+
+        if workers <= 1 {
+            work();
+        } else {
+            thread::scope(|scope| {
+                for _ in 0..workers {
+                    scope.spawn(work);
+                }
+            });
+        }
+        let mut reports = shared.into_inner().unwrap().reports;
+
+        // Anything that never ran either had a dependency that did not complete or was
+        // still waiting when the run was cancelled.
+        self.skipped.clear();
+        let mut children = Vec::with_capacity(count);
+        for index in order {
+            match reports[*index].take() {
+                Some(report) => children.push(report),
+                None if done[*index] => {}
+                None => {
+                    let id = self.nodes[*index].id;
+                    let blocker = dependencies[*index].iter().find(|dependency| {
+                        self.nodes[**dependency].job.status() != JobStatus::Completed
+                    });
+                    self.skipped.push(id);
+                    let reason = match blocker {
+                        Some(dependency) => format!(
+                            "Skipping job {}: dependency {} did not complete",
+                            id, self.nodes[*dependency].id
+                        ),
+                        None => format!("Skipping job {}: the graph was stopped", id),
+                    };
+                    children.push(JobReport::new(
+                        Some(id),
+                        self.nodes[*index].job.status(),
+                        reason,
+                    ));
+                }
+            }
+        }
+        children
+    }
+}
+
+impl Default for DagJob {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// State shared by the workers of one `DagJob::run`.
+struct Scheduler<'a> {
+    remaining: Vec<usize>,
+    ready: VecDeque<usize>,
+    slots: Vec<Option<&'a mut Box<dyn JobImpl>>>,
+    reports: Vec<Option<JobReport>>,
+    in_flight: usize,
+}
+
+fn dependents_of(dependencies: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut dependents = vec![Vec::new(); dependencies.len()];
+    for (index, node_dependencies) in dependencies.iter().enumerate() {
+        for dependency in node_dependencies {
+            dependents[*dependency].push(index);
+        }
+    }
+    dependents
+}
+
+impl JobImpl for DagJob {
+    fn run(&mut self, context: &JobContext) -> JobReport {
+        let started_at = context.now();
+        self.status = JobStatus::Running;
+        match self.topological_order() {
+            Ok(order) => {
+                let children = self.run_graph(&order, context);
+                match context.cancellation().reason() {
+                    Some(CancelReason::Paused) => self.status = JobStatus::Paused,
+                    Some(CancelReason::Stopped) => self.status = JobStatus::Stopped,
+                    _ => {}
+                }
+                JobReport::new(None, self.status(), "Running dependency graph:")
+                    .with_span(started_at, context.now())
+                    .with_children(children)
+            }
+            Err(error) => {
+                self.status = JobStatus::Failed;
+                JobReport::new(
+                    None,
+                    self.status,
+                    format!("Rejecting dependency graph: {}", error),
+                )
+            }
+        }
+    }
+
+    fn stop(&mut self) -> JobReport {
+        self.status = JobStatus::Stopped;
+        let children: Vec<JobReport> = self
+            .nodes
+            .iter_mut()
+            .filter(|node| node.job.status().can_transition_to(JobStatus::Stopped))
+            .map(|node| node.job.stop())
+            .collect();
+        JobReport::new(None, self.status(), "Stopping dependency graph:").with_children(children)
+    }
+
+    fn pause(&mut self) -> JobReport {
+        self.status = JobStatus::Paused;
+        let children: Vec<JobReport> = self
+            .nodes
+            .iter_mut()
+            .filter(|node| node.job.status() == JobStatus::Running)
+            .map(|node| node.job.pause())
+            .collect();
+        JobReport::new(None, self.status(), "Pausing dependency graph:").with_children(children)
+    }
+
+    // Undoes completed nodes in the reverse of the order they were scheduled in, so a job
+    // is rolled back before anything it depends on.
+    fn compensate(&mut self, context: &JobContext) -> Option<JobReport> {
+        let order = self.topological_order().ok()?;
+        let compensations: Vec<JobReport> = order
+            .iter()
+            .rev()
+            .filter_map(|index| {
+                let job = &mut self.nodes[*index].job;
+                if job.status() != JobStatus::Completed {
+                    return None;
+                }
+                job.compensate(&context.child(*index))
+            })
+            .collect();
+        compensation_group("Compensating dependency graph:", compensations)
+    }
+
+    fn status(&self) -> JobStatus {
+        // Only a rejected graph fails on its own; otherwise the nodes decide.
+        if self.status == JobStatus::Failed {
+            return JobStatus::Failed;
+        }
+        let statuses: Vec<JobStatus> = self.nodes.iter().map(|node| node.job.status()).collect();
+        let status = StatusRules::default()
+            .rollup(&statuses)
+            .unwrap_or(self.status);
+        if matches!(self.status, JobStatus::Paused | JobStatus::Stopped)
+            && status != JobStatus::Completed
+        {
+            return self.status;
+        }
+        status
+    }
+
+    fn outline(&self) -> JobOutline {
+        let children = self.nodes.iter().map(|node| node.job.outline()).collect();
+        let edges = self
+            .nodes
+            .iter()
+            .map(|node| (node.id, node.depends_on.clone()))
+            .collect();
+        JobOutline::group(self.status(), children).with_kind(GroupKind::Graph {
+            max_concurrency: self.max_concurrency,
+            edges,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{Job, SingleJob};
+    use std::sync::Arc;
+
+    fn recording(id: u32, log: &Arc<Mutex<Vec<u32>>>) -> Box<dyn JobImpl> {
+        let log = Arc::clone(log);
+        Box::new(SingleJob::with_task(id, move || {
+            log.lock().unwrap().push(id);
+            Ok::<_, String>("ok".to_string())
+        }))
+    }
+
+    #[test]
+    fn test_dag_runs_jobs_after_their_dependencies() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut dag = DagJob::new().with_max_concurrency(4);
+        dag.add(4, recording(4, &log), &[2, 3]).unwrap();
+        dag.add(2, recording(2, &log), &[1]).unwrap();
+        dag.add(3, recording(3, &log), &[1]).unwrap();
+        dag.add(1, recording(1, &log), &[]).unwrap();
+        assert_eq!(dag.schedule().unwrap(), vec![1, 2, 3, 4]);
+
+        let mut job = Job::new(Box::new(dag));
+        job.run().unwrap();
+        assert_eq!(job.status(), JobStatus::Completed);
+
+        let log = log.lock().unwrap();
+        let position = |id| log.iter().position(|logged| *logged == id).unwrap();
+        assert_eq!(log.len(), 4);
+        assert_eq!(position(1), 0);
+        assert_eq!(position(4), 3);
+    }
+
+    #[test]
+    fn test_dag_rejects_cycles_and_unknown_dependencies() {
+        let mut dag = DagJob::new();
+        dag.add(1, Box::new(SingleJob::new(1)), &[3]).unwrap();
+        dag.add(2, Box::new(SingleJob::new(2)), &[1]).unwrap();
+        dag.add(3, Box::new(SingleJob::new(3)), &[2]).unwrap();
+        assert_eq!(
+            dag.add(3, Box::new(SingleJob::new(3)), &[]),
+            Err(JobError::DuplicateJob(3))
+        );
+        assert_eq!(
+            dag.add(5, Box::new(SingleJob::new(4)), &[]),
+            Err(JobError::MismatchedId { key: 5, job: 4 })
+        );
+
+        let error = dag.schedule().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "dependency cycle between jobs 1 -> 3 -> 2 -> 1"
+        );
+
+        let mut job = Job::new(Box::new(dag));
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Rejecting dependency graph: dependency cycle between jobs 1 -> 3 -> 2 -> 1"
+        );
+        assert_eq!(job.status(), JobStatus::Failed);
+
+        let mut dag = DagJob::new();
+        dag.add(1, Box::new(SingleJob::new(1)), &[9]).unwrap();
+        assert_eq!(
+            dag.schedule(),
+            Err(JobError::UnknownDependency {
+                job: 1,
+                dependency: 9,
+            })
+        );
+    }
+
+    #[test]
+    fn test_dag_skips_jobs_downstream_of_a_failure() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut dag = DagJob::new();
+        dag.add(1, Box::new(SingleJob::with_task(1, || Err("boom"))), &[])
+            .unwrap();
+        dag.add(2, recording(2, &log), &[1]).unwrap();
+        dag.add(3, recording(3, &log), &[2]).unwrap();
+        dag.add(4, recording(4, &log), &[]).unwrap();
+
+        let expected_run_output = "Running dependency graph:\nSingle job 1 failed: boom\nSingle job 4 completed: ok\nSkipping job 2: dependency 1 did not complete\nSkipping job 3: dependency 2 did not complete";
+        assert_eq!(
+            dag.run(&JobContext::default()).to_string(),
+            expected_run_output
+        );
+        assert_eq!(dag.skipped(), &[2, 3]);
+        assert_eq!(dag.status(), JobStatus::Failed);
+        assert_eq!(*log.lock().unwrap(), vec![4]);
+    }
+
+    #[test]
+    fn test_dag_stopped_between_jobs_reports_stopped() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let stopping = SingleJob::with_context_task(1, |context: &JobContext| {
+            context.cancellation().cancel();
+            Ok::<_, String>("ok".to_string())
+        });
+        let mut dag = DagJob::new();
+        dag.add(1, Box::new(stopping), &[]).unwrap();
+        dag.add(2, recording(2, &log), &[1]).unwrap();
+        let mut job = Job::new(Box::new(dag));
+
+        assert_eq!(job.run().unwrap().status, JobStatus::Stopped);
+        assert_eq!(job.status(), JobStatus::Stopped);
+        job.run().unwrap();
+        assert_eq!(*log.lock().unwrap(), vec![2]);
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+}
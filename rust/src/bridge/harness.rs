@@ -0,0 +1,215 @@
+use super::clock::{Clock, ManualClock};
+use super::progress::JobEvent;
+use super::report::JobReport;
+use super::{Job, JobImpl, JobStatus};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Where the simulated clock of a harness starts by default: 2023-11-14 22:13:20 UTC.
+const START: Duration = Duration::from_secs(1_700_000_000);
+
+// One status change somewhere in the tree, at a time relative to the start of the harness.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub path: Vec<usize>,
+    pub status: JobStatus,
+    pub at: Duration,
+}
+
+// Runs a job tree on a simulated clock and records every status transition, so tests can
+// assert on what happened and when instead of on rendered output. Only the thread that
+// runs the job may sleep, so jobs that sleep in parallel are rejected; see `ManualClock`.
+pub struct JobHarness {
+    job: Job,
+    clock: Arc<ManualClock>,
+    started_at: SystemTime,
+    transitions: Arc<Mutex<Vec<Transition>>>,
+}
+
+impl JobHarness {
+    pub fn new(implementation: Box<dyn JobImpl>) -> Self {
+        Self::with_clock(
+            implementation,
+            Arc::new(ManualClock::new(UNIX_EPOCH + START)),
+        )
+    }
+
+    // For jobs that need the clock before the harness exists, such as ones sharing a rate
+    // limiter with it. Times are measured from the clock's current time.
+    pub fn with_clock(implementation: Box<dyn JobImpl>, clock: Arc<ManualClock>) -> Self {
+        let started_at = clock.now();
+        let job = Job::new(implementation).with_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+        let (recorded, recording_clock) = (Arc::clone(&transitions), Arc::clone(&clock));
+        job.subscribers().subscribe(move |event| {
+            if let JobEvent::StatusChanged { path, to, .. } = event {
+                recorded.lock().unwrap().push(Transition {
+                    path: path.clone(),
+                    status: *to,
+                    at: since(started_at, recording_clock.now()),
+                });
+            }
+        });
+        JobHarness {
+            job,
+            clock,
+            started_at,
+            transitions,
+        }
+    }
+
+    pub fn clock(&self) -> &ManualClock {
+        &self.clock
+    }
+
+    pub fn job(&mut self) -> &mut Job {
+        &mut self.job
+    }
+
+    pub fn run(&mut self) -> JobReport {
+        self.clock.confine_sleep_to(thread::current().id());
+        self.job.run().expect("the harnessed job could not start")
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        since(self.started_at, self.clock.now())
+    }
+
+    pub fn transitions(&self) -> Vec<Transition> {
+        self.transitions.lock().unwrap().clone()
+    }
+
+    // The statuses the job at `path` went through, in order.
+    pub fn statuses_of(&self, path: &[usize]) -> Vec<JobStatus> {
+        self.transitions()
+            .into_iter()
+            .filter(|transition| transition.path == path)
+            .map(|transition| transition.status)
+            .collect()
+    }
+
+    // Compares every recorded transition with `expected`, ignoring times. Panics with
+    // both sequences, one transition per line, when they differ.
+    #[track_caller]
+    pub fn assert_transitions(&self, expected: &[(&[usize], JobStatus)]) {
+        let actual: Vec<String> = self
+            .transitions()
+            .iter()
+            .map(|transition| describe(&transition.path, transition.status))
+            .collect();
+        let expected: Vec<String> = expected
+            .iter()
+            .map(|(path, status)| describe(path, *status))
+            .collect();
+        assert!(
+            actual == expected,
+            "status transitions differ\nexpected:\n  {}\nactual:\n  {}",
+            expected.join("\n  "),
+            actual.join("\n  ")
+        );
+    }
+}
+
+fn since(started_at: SystemTime, now: SystemTime) -> Duration {
+    now.duration_since(started_at).unwrap_or_default()
+}
+
+fn describe(path: &[usize], status: JobStatus) -> String {
+    let path: Vec<String> = path.iter().map(|index| index.to_string()).collect();
+    format!("/{} {:?}", path.join("/"), status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::retry::{Backoff, RetryJob, RetryPolicy};
+    use crate::bridge::timeout::TimeoutJob;
+    use crate::bridge::{JobContext, MultipleJob, SingleJob};
+    use JobStatus::*;
+
+    fn sleeper(id: u32, duration: Duration) -> Box<dyn JobImpl> {
+        Box::new(SingleJob::with_context_task(
+            id,
+            move |context: &JobContext| {
+                context.cancellation().sleep(duration);
+                context.checkpoint().map_err(|reason| reason.to_string())?;
+                Ok::<_, String>("slept".to_string())
+            },
+        ))
+    }
+
+    #[test]
+    fn test_group_transitions_in_simulated_time() {
+        let failing = SingleJob::with_task(2, || Err::<String, _>("boom"));
+        let group = MultipleJob::new(vec![
+            sleeper(1, Duration::from_secs(90)),
+            Box::new(failing),
+            sleeper(3, Duration::from_secs(30)),
+        ]);
+        let mut harness = JobHarness::new(Box::new(group));
+        let report = harness.run();
+
+        harness.assert_transitions(&[
+            (&[], Running),
+            (&[0], Running),
+            (&[0], Completed),
+            (&[1], Running),
+            (&[1], Failed),
+            (&[2], Running),
+            (&[2], Completed),
+            (&[], Failed),
+        ]);
+        assert_eq!(harness.elapsed(), Duration::from_secs(120));
+        assert_eq!(report.duration(), Duration::from_secs(120));
+        assert_eq!(
+            report.children[2].started_at,
+            harness.clock().now() - Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn test_timeout_fires_at_the_simulated_deadline() {
+        let slow = TimeoutJob::new(
+            sleeper(1, Duration::from_secs(3600)),
+            Duration::from_secs(5),
+        );
+        let mut harness = JobHarness::new(Box::new(MultipleJob::new(vec![Box::new(slow)])));
+        harness.run();
+
+        assert_eq!(harness.statuses_of(&[0]), vec![Running, Failed]);
+        assert_eq!(
+            harness.transitions().last().unwrap().at,
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn test_retry_delays_follow_the_backoff() {
+        let flaky = SingleJob::with_task(1, || Err::<String, _>("unavailable"));
+        let policy = RetryPolicy::new(3, Backoff::Fixed(Duration::from_secs(10)));
+        let retried = RetryJob::new(Box::new(flaky), policy);
+        let mut harness = JobHarness::new(Box::new(MultipleJob::new(vec![Box::new(retried)])));
+        harness.run();
+
+        let attempts: Vec<(JobStatus, Duration)> = harness
+            .transitions()
+            .into_iter()
+            .filter(|transition| transition.path == [0])
+            .map(|transition| (transition.status, transition.at))
+            .collect();
+        let at = Duration::from_secs;
+        assert_eq!(
+            attempts,
+            vec![
+                (Running, at(0)),
+                (Failed, at(0)),
+                (Running, at(10)),
+                (Failed, at(10)),
+                (Running, at(20)),
+                (Failed, at(20)),
+            ]
+        );
+        assert_eq!(harness.job().status(), Failed);
+    }
+}
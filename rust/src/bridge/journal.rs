@@ -0,0 +1,618 @@
+use super::dag::DagJob;
+use super::{
+    FailurePolicy, GroupKind, JobError, JobImpl, JobOutline, JobStatus, MultipleJob, SingleJob,
+    StatusRules,
+};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+// One line of the journal. Paths locate a job by the child index taken at each level
+// below the root, written as `/`, `/0`, `/0/2` and so on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEntry {
+    // Starts a new snapshot; recovery only looks at entries after the last one.
+    Begin,
+    Group {
+        path: Vec<usize>,
+        status: JobStatus,
+        kind: GroupKind,
+    },
+    Job {
+        path: Vec<usize>,
+        id: u32,
+        status: JobStatus,
+    },
+    Transition {
+        path: Vec<usize>,
+        status: JobStatus,
+    },
+}
+
+impl JournalEntry {
+    fn encode(&self) -> String {
+        match self {
+            JournalEntry::Begin => "begin".to_string(),
+            JournalEntry::Group { path, status, kind } => {
+                format!(
+                    "group {} {:?} {}",
+                    encode_path(path),
+                    status,
+                    encode_kind(kind)
+                )
+            }
+            JournalEntry::Job { path, id, status } => {
+                format!("job {} {} {:?}", encode_path(path), id, status)
+            }
+            JournalEntry::Transition { path, status } => {
+                format!("status {} {:?}", encode_path(path), status)
+            }
+        }
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(' ').collect();
+        match fields.as_slice() {
+            ["begin"] => Some(JournalEntry::Begin),
+            ["group", path, status, kind @ ..] => Some(JournalEntry::Group {
+                path: decode_path(path)?,
+                status: status.parse().ok()?,
+                kind: decode_kind(kind)?,
+            }),
+            ["job", path, id, status] => Some(JournalEntry::Job {
+                path: decode_path(path)?,
+                id: id.parse().ok()?,
+                status: status.parse().ok()?,
+            }),
+            ["status", path, status] => Some(JournalEntry::Transition {
+                path: decode_path(path)?,
+                status: status.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn encode_path(path: &[usize]) -> String {
+    let parts: Vec<String> = path.iter().map(|index| index.to_string()).collect();
+    format!("/{}", parts.join("/"))
+}
+
+fn decode_path(encoded: &str) -> Option<Vec<usize>> {
+    let rest = encoded.strip_prefix('/')?;
+    if rest.is_empty() {
+        return Some(Vec::new());
+    }
+    rest.split('/').map(|part| part.parse().ok()).collect()
+}
+
+// Group settings are written as space-separated fields after the group's status:
+// `sequence <precedence> <concurrency> <failure policy> <limited|unlimited>` or
+// `graph <concurrency> <key:dependencies;...>`, with `-` standing for an empty list.
+fn encode_kind(kind: &GroupKind) -> String {
+    match kind {
+        GroupKind::Sequence {
+            rules,
+            max_concurrency,
+            failure_policy,
+            rate_limited,
+        } => {
+            let precedence: Vec<String> = rules
+                .precedence
+                .iter()
+                .map(|status| format!("{:?}", status))
+                .collect();
+            let policy = match failure_policy {
+                FailurePolicy::FailFast => "fail-fast".to_string(),
+                FailurePolicy::ContinueOnError => "continue-on-error".to_string(),
+                FailurePolicy::TolerateFailures(limit) => format!("tolerate-{}", limit),
+            };
+            let limited = if *rate_limited {
+                "limited"
+            } else {
+                "unlimited"
+            };
+            format!(
+                "sequence {} {} {} {}",
+                encode_list(&precedence, ","),
+                max_concurrency,
+                policy,
+                limited
+            )
+        }
+        GroupKind::Graph {
+            max_concurrency,
+            edges,
+        } => {
+            let edges: Vec<String> = edges
+                .iter()
+                .map(|(key, depends_on)| {
+                    let depends_on: Vec<String> = depends_on.iter().map(u32::to_string).collect();
+                    format!("{}:{}", key, depends_on.join(","))
+                })
+                .collect();
+            format!("graph {} {}", max_concurrency, encode_list(&edges, ";"))
+        }
+    }
+}
+
+fn decode_kind(fields: &[&str]) -> Option<GroupKind> {
+    match fields {
+        ["sequence", precedence, max_concurrency, policy, limited] => {
+            let precedence = decode_list(precedence, ',')
+                .map(|status| status.parse().ok())
+                .collect::<Option<Vec<JobStatus>>>()?;
+            let failure_policy = match *policy {
+                "fail-fast" => FailurePolicy::FailFast,
+                "continue-on-error" => FailurePolicy::ContinueOnError,
+                other => {
+                    FailurePolicy::TolerateFailures(other.strip_prefix("tolerate-")?.parse().ok()?)
+                }
+            };
+            let rate_limited = match *limited {
+                "limited" => true,
+                "unlimited" => false,
+                _ => return None,
+            };
+            Some(GroupKind::Sequence {
+                rules: StatusRules::new(precedence),
+                max_concurrency: max_concurrency.parse().ok()?,
+                failure_policy,
+                rate_limited,
+            })
+        }
+        ["graph", max_concurrency, edges] => {
+            let edges = decode_list(edges, ';')
+                .map(|edge| {
+                    let (key, depends_on) = edge.split_once(':')?;
+                    let depends_on = decode_list(depends_on, ',')
+                        .map(|key| key.parse().ok())
+                        .collect::<Option<Vec<u32>>>()?;
+                    Some((key.parse().ok()?, depends_on))
+                })
+                .collect::<Option<Vec<(u32, Vec<u32>)>>>()?;
+            Some(GroupKind::Graph {
+                max_concurrency: max_concurrency.parse().ok()?,
+                edges,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn encode_list(items: &[String], separator: &str) -> String {
+    if items.is_empty() {
+        "-".to_string()
+    } else {
+        items.join(separator)
+    }
+}
+
+fn decode_list(encoded: &str, separator: char) -> impl Iterator<Item = &str> {
+    encoded
+        .split(separator)
+        .filter(move |item| !item.is_empty() && *item != "-")
+}
+
+// An append-only, write-ahead log of job trees and their status transitions. Every
+// entry is flushed to disk before the job it describes moves on.
+pub struct Journal {
+    file: Mutex<File>,
+}
+
+impl Journal {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Journal {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn append(&self, entry: &JournalEntry) -> io::Result<()> {
+        self.write_lines(&[entry.encode()])
+    }
+
+    // Writes the whole tree with its current statuses as a fresh starting point.
+    pub fn snapshot(&self, outline: &JobOutline) -> io::Result<()> {
+        let mut entries = vec![JournalEntry::Begin];
+        flatten(outline, &mut Vec::new(), &mut entries);
+        let lines: Vec<String> = entries.iter().map(JournalEntry::encode).collect();
+        self.write_lines(&lines)
+    }
+
+    fn write_lines(&self, lines: &[String]) -> io::Result<()> {
+        let mut buffer = lines.join("\n");
+        buffer.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(buffer.as_bytes())?;
+        file.sync_data()
+    }
+
+    // Rebuilds the outline of the last journaled tree with the last status seen for every
+    // job. Returns `None` if the journal holds no snapshot.
+    pub fn recover(path: impl AsRef<Path>) -> io::Result<Option<JobOutline>> {
+        let contents = fs::read_to_string(path)?;
+        let lines: Vec<&str> = contents.lines().collect();
+
+        let mut root: Option<JobOutline> = None;
+        for (number, line) in lines.iter().enumerate() {
+            let Some(entry) = JournalEntry::decode(line) else {
+                // A crash can tear the final write; anything earlier is real corruption.
+                if number + 1 == lines.len() {
+                    break;
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("malformed journal entry on line {}: {}", number + 1, line),
+                ));
+            };
+
+            match entry {
+                JournalEntry::Begin => root = None,
+                JournalEntry::Group { path, status, kind } => insert(
+                    &mut root,
+                    &path,
+                    JobOutline::group(status, Vec::new()).with_kind(kind),
+                ),
+                JournalEntry::Job { path, id, status } => {
+                    insert(&mut root, &path, JobOutline::leaf(id, status))
+                }
+                JournalEntry::Transition { path, status } => {
+                    if let Some(node) = root.as_mut().and_then(|root| find(root, &path)) {
+                        node.status = status;
+                    }
+                }
+            }
+        }
+
+        if let Some(root) = root.as_mut() {
+            refresh_group_statuses(root);
+        }
+        Ok(root)
+    }
+}
+
+fn flatten(outline: &JobOutline, path: &mut Vec<usize>, entries: &mut Vec<JournalEntry>) {
+    entries.push(match outline.id {
+        Some(id) => JournalEntry::Job {
+            path: path.clone(),
+            id,
+            status: outline.status,
+        },
+        None => JournalEntry::Group {
+            path: path.clone(),
+            status: outline.status,
+            kind: outline.kind.clone().unwrap_or_default(),
+        },
+    });
+    for (index, child) in outline.children.iter().enumerate() {
+        path.push(index);
+        flatten(child, path, entries);
+        path.pop();
+    }
+}
+
+// Snapshots are written parent first, so a node's parent always exists by the time it
+// is inserted.
+fn insert(root: &mut Option<JobOutline>, path: &[usize], node: JobOutline) {
+    let Some((_, parent_path)) = path.split_last() else {
+        *root = Some(node);
+        return;
+    };
+    if let Some(parent) = root.as_mut().and_then(|root| find(root, parent_path)) {
+        parent.children.push(node);
+    }
+}
+
+fn find<'a>(node: &'a mut JobOutline, path: &[usize]) -> Option<&'a mut JobOutline> {
+    match path.split_first() {
+        None => Some(node),
+        Some((index, rest)) => find(node.children.get_mut(*index)?, rest),
+    }
+}
+
+fn refresh_group_statuses(node: &mut JobOutline) {
+    if !node.is_group() {
+        return;
+    }
+    for child in &mut node.children {
+        refresh_group_statuses(child);
+    }
+    let statuses: Vec<JobStatus> = node.children.iter().map(|child| child.status).collect();
+    // Graphs always roll up with the default rules.
+    let rules = match &node.kind {
+        Some(GroupKind::Sequence { rules, .. }) => rules.clone(),
+        _ => StatusRules::default(),
+    };
+    if let Some(status) = rules.rollup(&statuses) {
+        node.status = status;
+    }
+}
+
+// What to do with jobs that were still `Running` when the process died.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryAction {
+    // Run them again, using the work `rebuild`'s factory supplies for their id.
+    Resume,
+    MarkFailed,
+}
+
+// Turns a recovered outline back into a runnable tree. Groups come back as the
+// `MultipleJob` or `DagJob` they were journaled as, with the same settings; `factory`
+// supplies the work for every job that still has to run, and jobs it has no work for keep
+// their journaled status. Groups that relied on a rate limiter are rejected, since the
+// limiter itself is not journaled.
+pub fn rebuild<F>(
+    outline: &JobOutline,
+    action: RecoveryAction,
+    factory: &mut F,
+) -> Result<Box<dyn JobImpl>, JobError>
+where
+    F: FnMut(u32) -> Option<Box<dyn JobImpl>>,
+{
+    let Some(id) = outline.id else {
+        return rebuild_group(outline, action, factory);
+    };
+
+    Ok(match (outline.status, action) {
+        (JobStatus::Completed, _) => Box::new(SingleJob::restored(id, JobStatus::Completed)),
+        (JobStatus::Running, RecoveryAction::MarkFailed) => {
+            Box::new(SingleJob::restored(id, JobStatus::Failed))
+        }
+        (status, _) => factory(id).unwrap_or_else(|| {
+            let status = if status == JobStatus::Running {
+                JobStatus::Failed
+            } else {
+                status
+            };
+            Box::new(SingleJob::restored(id, status))
+        }),
+    })
+}
+
+fn rebuild_group<F>(
+    outline: &JobOutline,
+    action: RecoveryAction,
+    factory: &mut F,
+) -> Result<Box<dyn JobImpl>, JobError>
+where
+    F: FnMut(u32) -> Option<Box<dyn JobImpl>>,
+{
+    let mut children = Vec::new();
+    for child in &outline.children {
+        children.push(rebuild(child, action, factory)?);
+    }
+
+    match outline.kind.clone().unwrap_or_default() {
+        GroupKind::Sequence { rate_limited, .. } if rate_limited => {
+            Err(JobError::Unrecoverable(format!(
+                "a group of jobs {:?} ran under a rate limiter",
+                outline.ids()
+            )))
+        }
+        GroupKind::Sequence {
+            rules,
+            max_concurrency,
+            failure_policy,
+            ..
+        } => Ok(Box::new(
+            MultipleJob::new(children)
+                .with_status_rules(rules)
+                .with_max_concurrency(max_concurrency)
+                .with_failure_policy(failure_policy),
+        )),
+        GroupKind::Graph {
+            max_concurrency,
+            edges,
+        } => {
+            if edges.len() != children.len() {
+                return Err(JobError::Unrecoverable(format!(
+                    "a graph of jobs {:?} has {} edges for {} jobs",
+                    outline.ids(),
+                    edges.len(),
+                    children.len()
+                )));
+            }
+            let mut dag = DagJob::new().with_max_concurrency(max_concurrency);
+            for ((key, depends_on), child) in edges.into_iter().zip(children) {
+                dag.add(key, child, &depends_on)?;
+            }
+            Ok(Box::new(dag))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::Job;
+    use crate::bridge::rate::RateLimiter;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, mpsc};
+    use std::time::Duration;
+
+    fn journal_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "bridge-journal-{}-{}.log",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn counted(id: u32, runs: &Arc<AtomicUsize>) -> Box<dyn JobImpl> {
+        let runs = Arc::clone(runs);
+        Box::new(SingleJob::with_task(id, move || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, String>("ok".to_string())
+        }))
+    }
+
+    #[test]
+    fn test_recovery_sees_jobs_running_at_crash() {
+        let path = journal_path("crash");
+        let journal = Arc::new(Journal::open(&path).unwrap());
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        // Job 2 reads the journal while it is running, which is what a fresh process
+        // would find if this one died at that moment.
+        let (sender, receiver) = mpsc::channel();
+        let snooping_path = path.clone();
+        let snooping = SingleJob::with_task(2, move || {
+            sender.send(Journal::recover(&snooping_path)).unwrap();
+            Ok::<_, String>("ok".to_string())
+        });
+        let inner = MultipleJob::new(vec![Box::new(snooping), counted(3, &runs)]);
+        let tree = MultipleJob::new(vec![counted(1, &runs), Box::new(inner)]);
+        Job::new(Box::new(tree))
+            .with_journal(journal)
+            .run()
+            .unwrap();
+
+        let outline = receiver.recv().unwrap().unwrap().unwrap();
+        assert_eq!(outline.ids_with_status(JobStatus::Completed), vec![1]);
+        assert_eq!(outline.ids_with_status(JobStatus::Running), vec![2]);
+        assert_eq!(outline.ids_with_status(JobStatus::Pending), vec![3]);
+        assert_eq!(outline.status, JobStatus::Running);
+        assert!(outline.children[1].is_group());
+
+        // Resuming reruns the interrupted and pending jobs but not the completed one.
+        let runs_before = runs.load(Ordering::SeqCst);
+        let resumed_runs = Arc::clone(&runs);
+        let mut factory = |id| Some(counted(id, &resumed_runs));
+        let rebuilt = rebuild(&outline, RecoveryAction::Resume, &mut factory).unwrap();
+        assert_eq!(
+            rebuilt.outline().ids_with_status(JobStatus::Completed),
+            vec![1]
+        );
+
+        let mut job = Job::new(rebuilt);
+        job.run().unwrap();
+        assert_eq!(job.status(), JobStatus::Completed);
+        assert_eq!(runs.load(Ordering::SeqCst) - runs_before, 2);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_interrupted_jobs_can_be_marked_failed() {
+        let path = journal_path("mark-failed");
+        let journal = Journal::open(&path).unwrap();
+        let outline = JobOutline::group(
+            JobStatus::Pending,
+            vec![
+                JobOutline::leaf(1, JobStatus::Pending),
+                JobOutline::leaf(2, JobStatus::Pending),
+            ],
+        );
+        journal.snapshot(&outline).unwrap();
+        journal
+            .append(&JournalEntry::Transition {
+                path: vec![0],
+                status: JobStatus::Running,
+            })
+            .unwrap();
+        // A torn final write is ignored.
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"status /1 Compl")
+            .unwrap();
+
+        let outline = Journal::recover(&path).unwrap().unwrap();
+        assert_eq!(outline.ids_with_status(JobStatus::Running), vec![1]);
+
+        let mut factory = |_| None;
+        let rebuilt = rebuild(&outline, RecoveryAction::MarkFailed, &mut factory).unwrap();
+        assert_eq!(
+            rebuilt.outline().ids_with_status(JobStatus::Failed),
+            vec![1]
+        );
+        assert_eq!(rebuilt.status(), JobStatus::Failed);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_groups_are_rebuilt_with_their_kind_and_settings() {
+        let path = journal_path("kinds");
+        let journal = Journal::open(&path).unwrap();
+        let mut graph = DagJob::new().with_max_concurrency(2);
+        graph
+            .add(
+                3,
+                Box::new(SingleJob::restored(3, JobStatus::Completed)),
+                &[],
+            )
+            .unwrap();
+        graph.add(4, Box::new(SingleJob::new(4)), &[3]).unwrap();
+        let sequence = MultipleJob::new(vec![Box::new(SingleJob::new(1)), Box::new(graph)])
+            .with_status_rules(StatusRules::new(vec![
+                JobStatus::Running,
+                JobStatus::Failed,
+            ]))
+            .with_max_concurrency(3)
+            .with_failure_policy(FailurePolicy::TolerateFailures(2));
+        let original = sequence.outline();
+        journal.snapshot(&original).unwrap();
+
+        let outline = Journal::recover(&path).unwrap().unwrap();
+        assert_eq!(outline, original);
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut factory = |id| Some(counted(id, &runs));
+        let rebuilt = rebuild(&outline, RecoveryAction::Resume, &mut factory).unwrap();
+        assert_eq!(rebuilt.outline(), original);
+
+        let mut job = Job::new(rebuilt);
+        job.run().unwrap();
+        assert_eq!(
+            job.outline().ids_with_status(JobStatus::Completed),
+            vec![1, 3, 4]
+        );
+        // Job 3 had already completed, so only 1 and 4 ran again.
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_rate_limited_groups_are_not_rebuilt() {
+        let limiter = Arc::new(RateLimiter::token_bucket(1, Duration::from_secs(1)));
+        let outline = MultipleJob::new(vec![Box::new(SingleJob::new(1))])
+            .with_rate_limiter(limiter)
+            .outline();
+        let entry = JournalEntry::Group {
+            path: Vec::new(),
+            status: JobStatus::Pending,
+            kind: outline.kind.clone().unwrap(),
+        };
+        assert_eq!(JournalEntry::decode(&entry.encode()), Some(entry));
+
+        let mut factory = |_| None;
+        assert!(matches!(
+            rebuild(&outline, RecoveryAction::Resume, &mut factory),
+            Err(JobError::Unrecoverable(_))
+        ));
+    }
+
+    #[test]
+    fn test_stop_is_journaled() {
+        let path = journal_path("stop");
+        let journal = Arc::new(Journal::open(&path).unwrap());
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![
+            Box::new(SingleJob::new(1)),
+            Box::new(SingleJob::new(2)),
+        ])))
+        .with_journal(journal);
+
+        job.run().unwrap();
+        job.stop().unwrap();
+
+        let outline = Journal::recover(&path).unwrap().unwrap();
+        assert_eq!(outline.ids_with_status(JobStatus::Stopped), vec![1, 2]);
+        let _ = fs::remove_file(&path);
+    }
+}
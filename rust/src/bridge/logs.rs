@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+// Lines kept per job unless a capacity is given.
+const DEFAULT_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    // Order of the line among all lines of the tree, so buffers can be interleaved.
+    pub sequence: u64,
+    pub path: Vec<usize>,
+    pub at: SystemTime,
+    pub message: String,
+}
+
+impl fmt::Display for LogLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|index| index.to_string()).collect();
+        write!(f, "/{} {}", path.join("/"), self.message)
+    }
+}
+
+struct LogState {
+    capacity: usize,
+    next_sequence: u64,
+    buffers: BTreeMap<Vec<usize>, VecDeque<LogLine>>,
+    dropped: u64,
+    spill: Option<File>,
+}
+
+// Log lines written by the jobs of one tree, in a bounded ring buffer per job. Clones
+// share the buffers, so a handle kept by the caller sees lines while the job runs.
+#[derive(Clone)]
+pub struct JobLogs {
+    state: Arc<Mutex<LogState>>,
+}
+
+impl JobLogs {
+    pub fn new(capacity: usize) -> Self {
+        JobLogs {
+            state: Arc::new(Mutex::new(LogState {
+                capacity: capacity.max(1),
+                next_sequence: 0,
+                buffers: BTreeMap::new(),
+                dropped: 0,
+                spill: None,
+            })),
+        }
+    }
+
+    // Also appends every line to `path`, including those the buffers later evict.
+    pub fn with_spill(self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.state.lock().unwrap().spill = Some(file);
+        Ok(self)
+    }
+
+    // A log file that cannot be written must not fail the job writing to it.
+    pub fn write(&self, path: &[usize], at: SystemTime, message: impl Into<String>) {
+        let mut state = self.state.lock().unwrap();
+        let line = LogLine {
+            sequence: state.next_sequence,
+            path: path.to_vec(),
+            at,
+            message: message.into(),
+        };
+        state.next_sequence += 1;
+        if let Some(spill) = state.spill.as_mut() {
+            let _ = writeln!(spill, "{}", line);
+        }
+
+        let capacity = state.capacity;
+        let buffer = state.buffers.entry(line.path.clone()).or_default();
+        let evicted = buffer.len() >= capacity;
+        if evicted {
+            buffer.pop_front();
+        }
+        buffer.push_back(line);
+        if evicted {
+            state.dropped += 1;
+        }
+    }
+
+    // Every buffered line of the tree, in the order they were written.
+    pub fn lines(&self) -> Vec<LogLine> {
+        self.lines_for(&[])
+    }
+
+    // The buffered lines of the job at `path` and of the jobs below it, interleaved.
+    pub fn lines_for(&self, path: &[usize]) -> Vec<LogLine> {
+        let state = self.state.lock().unwrap();
+        let mut lines: Vec<LogLine> = state
+            .buffers
+            .iter()
+            .filter(|(job, _)| job.starts_with(path))
+            .flat_map(|(_, buffer)| buffer.iter().cloned())
+            .collect();
+        lines.sort_by_key(|line| line.sequence);
+        lines
+    }
+
+    // Lines evicted from full buffers since the logs were created.
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+}
+
+impl Default for JobLogs {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::command::CommandJob;
+    use crate::bridge::{Job, JobContext, JobImpl, JobStatus, MultipleJob, SingleJob};
+    use std::fs;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn messages(lines: &[LogLine]) -> Vec<String> {
+        lines.iter().map(LogLine::to_string).collect()
+    }
+
+    fn chatty(id: u32, lines: usize) -> Box<dyn JobImpl> {
+        Box::new(SingleJob::with_context_task(
+            id,
+            move |context: &JobContext| {
+                for line in 0..lines {
+                    context.log(format!("job {} line {}", id, line));
+                }
+                Ok::<_, String>("ok".to_string())
+            },
+        ))
+    }
+
+    #[test]
+    fn test_buffers_keep_the_latest_lines_of_each_job() {
+        let logs = JobLogs::new(2);
+        let group = MultipleJob::new(vec![chatty(1, 3), chatty(2, 1)]);
+        let mut job = Job::new(Box::new(group)).with_logs(logs.clone());
+        job.run().unwrap();
+
+        assert_eq!(
+            messages(&logs.lines()),
+            vec!["/0 job 1 line 1", "/0 job 1 line 2", "/1 job 2 line 0"]
+        );
+        assert_eq!(
+            messages(&job.logs().lines_for(&[1])),
+            vec!["/1 job 2 line 0"]
+        );
+        assert_eq!(logs.dropped(), 1);
+    }
+
+    #[test]
+    fn test_lines_are_readable_while_the_job_runs() {
+        let (release, released) = mpsc::channel::<()>();
+        let (logged, written) = mpsc::channel::<()>();
+        let blocking = SingleJob::with_context_task(1, move |context: &JobContext| {
+            context.log("started");
+            let _ = logged.send(());
+            let _ = released.recv();
+            context.log("finished");
+            Ok::<_, String>("ok".to_string())
+        });
+        let commands = CommandJob::new(2, "sh").args(["-c", "echo out; echo err >&2"]);
+        let group = MultipleJob::new(vec![Box::new(blocking), Box::new(commands)]);
+        let mut job = Job::new(Box::new(group));
+        let logs = job.logs();
+
+        let runner = thread::spawn(move || job.run().unwrap().status);
+        written.recv().unwrap();
+        assert_eq!(messages(&logs.lines()), vec!["/0 started"]);
+        drop(release);
+        assert_eq!(runner.join().unwrap(), JobStatus::Completed);
+
+        let mut command_lines = messages(&logs.lines_for(&[1]));
+        command_lines.sort();
+        assert_eq!(command_lines, vec!["/1 out", "/1 stderr: err"]);
+        assert_eq!(messages(&logs.lines_for(&[0]))[1], "/0 finished");
+    }
+
+    #[test]
+    fn test_spill_file_keeps_evicted_lines() {
+        let path = std::env::temp_dir().join(format!("job-logs-{}.log", std::process::id()));
+        let logs = JobLogs::new(1).with_spill(&path).unwrap();
+        let mut job = Job::new(chatty(1, 3)).with_logs(logs.clone());
+        job.run().unwrap();
+
+        assert_eq!(messages(&logs.lines()), vec!["/ job 1 line 2"]);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "/ job 1 line 0\n/ job 1 line 1\n/ job 1 line 2\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
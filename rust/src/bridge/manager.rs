@@ -0,0 +1,375 @@
+use super::cancel::CancellationToken;
+use super::progress::JobEvent;
+use super::queue::panic_message;
+use super::report::JobReport;
+use super::{Job, JobError, JobStatus};
+use std::collections::{BTreeMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+
+struct Entry {
+    job: Arc<Mutex<Job>>,
+    // Kept up to date by a status subscriber, so queries never wait for a running job.
+    status: Arc<Mutex<JobStatus>>,
+    cancellation: CancellationToken,
+    leaf_ids: Vec<u32>,
+    // The report of the latest run or resume, outside the job's lock like `status`.
+    last_report: Arc<Mutex<Option<JobReport>>>,
+}
+
+// Owns a set of jobs and hands out an id for each. Every method takes `&self`, so one
+// manager can be shared between the threads running jobs and the threads watching them.
+#[derive(Default)]
+pub struct JobManager {
+    entries: Mutex<BTreeMap<u32, Entry>>,
+    // Ids are never reused, even after a job is removed.
+    last_id: AtomicU32,
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Rejects a job whose tree reuses the id of a job already registered here, or that
+    // uses the same id twice itself.
+    pub fn register(&self, job: Job) -> Result<u32, JobError> {
+        let leaf_ids = job.outline().ids();
+        let mut entries = self.entries.lock().unwrap();
+        let mut seen: HashSet<u32> = entries
+            .values()
+            .flat_map(|entry| entry.leaf_ids.iter().copied())
+            .collect();
+        if let Some(duplicate) = leaf_ids.iter().find(|id| !seen.insert(**id)) {
+            return Err(JobError::DuplicateJob(*duplicate));
+        }
+
+        let status = Arc::new(Mutex::new(job.status()));
+        let tracked = Arc::clone(&status);
+        job.subscribers().subscribe(move |event| {
+            if let JobEvent::StatusChanged { path, to, .. } = event
+                && path.is_empty()
+            {
+                *tracked.lock().unwrap() = *to;
+            }
+        });
+
+        let id = self.last_id.fetch_add(1, Ordering::SeqCst) + 1;
+        entries.insert(
+            id,
+            Entry {
+                cancellation: job.cancellation_token(),
+                job: Arc::new(Mutex::new(job)),
+                status,
+                leaf_ids,
+                last_report: Arc::new(Mutex::new(None)),
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn remove(&self, id: u32) -> Option<Arc<Mutex<Job>>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .map(|entry| entry.job)
+    }
+
+    // The job itself, for callers that need more than the manager offers. Its lock is
+    // held for the whole of a run.
+    pub fn get(&self, id: u32) -> Option<Arc<Mutex<Job>>> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(&id).map(|entry| Arc::clone(&entry.job))
+    }
+
+    pub fn status(&self, id: u32) -> Option<JobStatus> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(&id).map(|entry| *entry.status.lock().unwrap())
+    }
+
+    pub fn ids(&self) -> Vec<u32> {
+        self.entries.lock().unwrap().keys().copied().collect()
+    }
+
+    pub fn ids_with_status(&self, status: JobStatus) -> Vec<u32> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|(_, entry)| *entry.status.lock().unwrap() == status)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub fn failed(&self) -> Vec<u32> {
+        self.ids_with_status(JobStatus::Failed)
+    }
+
+    // Runs the job on the calling thread; other threads can keep querying meanwhile.
+    pub fn run(&self, id: u32) -> Result<JobReport, JobError> {
+        self.run_with(id, Job::run)
+    }
+
+    // Continues a paused job on the calling thread, like `run`.
+    pub fn resume(&self, id: u32) -> Result<JobReport, JobError> {
+        self.run_with(id, Job::resume)
+    }
+
+    pub fn report(&self, id: u32) -> Option<JobReport> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(&id)?;
+        entry.last_report.lock().unwrap().clone()
+    }
+
+    fn run_with(
+        &self,
+        id: u32,
+        run: impl FnOnce(&mut Job) -> Result<JobReport, JobError>,
+    ) -> Result<JobReport, JobError> {
+        let (job, last_report) = {
+            let entries = self.entries.lock().unwrap();
+            let entry = entries.get(&id).ok_or(JobError::UnknownJob(id))?;
+            (Arc::clone(&entry.job), Arc::clone(&entry.last_report))
+        };
+        let mut job = job.lock().unwrap_or_else(PoisonError::into_inner);
+        // A panicking job must not take its caller down with it. It is stopped, so it
+        // can be run again.
+        let report =
+            panic::catch_unwind(AssertUnwindSafe(|| run(&mut job))).unwrap_or_else(|payload| {
+                let _ = job.stop();
+                Err(JobError::Panicked(panic_message(payload.as_ref())))
+            })?;
+        *last_report.lock().unwrap() = Some(report.clone());
+        Ok(report)
+    }
+
+    pub fn stop(&self, id: u32) -> Result<(), JobError> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(&id).ok_or(JobError::UnknownJob(id))?;
+        Self::stop_entry(entry)
+    }
+
+    pub fn pause(&self, id: u32) -> Result<(), JobError> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(&id).ok_or(JobError::UnknownJob(id))?;
+        match idle(entry) {
+            Some(mut job) => job.pause().map(|_| ()),
+            None => {
+                entry.cancellation.pause();
+                Ok(())
+            }
+        }
+    }
+
+    // Stops every job that is currently running and returns their ids.
+    pub fn stop_all_running(&self) -> Vec<u32> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|(_, entry)| *entry.status.lock().unwrap() == JobStatus::Running)
+            .filter(|(_, entry)| Self::stop_entry(entry).is_ok())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // A job in the middle of `run` is locked by the thread running it, so it is stopped
+    // through its token and reports `Stopped` once the run returns. Pausing works the same.
+    // Stopping an idle job, such as a paused one, replaces its last report.
+    fn stop_entry(entry: &Entry) -> Result<(), JobError> {
+        match idle(entry) {
+            Some(mut job) => {
+                let report = job.stop()?;
+                *entry.last_report.lock().unwrap() = Some(report);
+                Ok(())
+            }
+            None => {
+                entry.cancellation.cancel();
+                Ok(())
+            }
+        }
+    }
+}
+
+// The job of `entry` unless a run holds it. A lock poisoned by an earlier panic does not
+// mean the job is running.
+fn idle(entry: &Entry) -> Option<MutexGuard<'_, Job>> {
+    match entry.job.try_lock() {
+        Ok(job) => Some(job),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{JobContext, MultipleJob, SingleJob};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn single(id: u32, succeeds: bool) -> Job {
+        Job::new(Box::new(SingleJob::with_task(id, move || {
+            if succeeds {
+                Ok("ok".to_string())
+            } else {
+                Err("boom")
+            }
+        })))
+    }
+
+    #[test]
+    fn test_register_assigns_ids_and_rejects_duplicates() {
+        let manager = JobManager::new();
+        assert_eq!(manager.register(single(1, true)), Ok(1));
+        let group = MultipleJob::new(vec![
+            Box::new(SingleJob::new(2)),
+            Box::new(SingleJob::new(3)),
+        ]);
+        assert_eq!(manager.register(Job::new(Box::new(group))), Ok(2));
+
+        assert_eq!(
+            manager.register(single(3, true)),
+            Err(JobError::DuplicateJob(3))
+        );
+        let twice = MultipleJob::new(vec![
+            Box::new(SingleJob::new(4)),
+            Box::new(SingleJob::new(4)),
+        ]);
+        assert_eq!(
+            manager.register(Job::new(Box::new(twice))),
+            Err(JobError::DuplicateJob(4))
+        );
+        assert_eq!(manager.ids(), vec![1, 2]);
+        assert_eq!(manager.run(7), Err(JobError::UnknownJob(7)));
+        assert!(manager.get(7).is_none());
+
+        // Removing a job frees its leaf ids but not its manager id.
+        assert!(manager.remove(1).is_some());
+        assert_eq!(manager.register(single(1, true)), Ok(3));
+    }
+
+    #[test]
+    fn test_query_by_status() {
+        let manager = JobManager::new();
+        let first = manager.register(single(1, true)).unwrap();
+        let second = manager.register(single(2, false)).unwrap();
+        let third = manager.register(single(3, false)).unwrap();
+
+        manager.run(first).unwrap();
+        manager.run(second).unwrap();
+        assert_eq!(manager.status(first), Some(JobStatus::Completed));
+        assert_eq!(manager.failed(), vec![second]);
+        assert_eq!(manager.ids_with_status(JobStatus::Pending), vec![third]);
+
+        let idle = manager
+            .register(Job::new(Box::new(SingleJob::new(4))))
+            .unwrap();
+        manager.run(idle).unwrap();
+        assert_eq!(manager.ids_with_status(JobStatus::Running), vec![idle]);
+        manager.stop(idle).unwrap();
+        assert_eq!(manager.status(idle), Some(JobStatus::Stopped));
+    }
+
+    #[test]
+    fn test_stop_all_running_reaches_jobs_on_other_threads() {
+        let manager = Arc::new(JobManager::new());
+        let waiting = Job::new(Box::new(SingleJob::with_context_task(
+            1,
+            |context: &JobContext| {
+                context.cancellation().sleep(Duration::from_secs(30));
+                context.checkpoint().map_err(|reason| reason.to_string())?;
+                Ok::<_, String>("finished".to_string())
+            },
+        )));
+        let id = manager.register(waiting).unwrap();
+        manager.register(single(2, true)).unwrap();
+
+        let worker = {
+            let manager = Arc::clone(&manager);
+            thread::spawn(move || manager.run(id).unwrap().to_string())
+        };
+        let started = Instant::now();
+        while manager.status(id) != Some(JobStatus::Running) {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(manager.stop_all_running(), vec![id]);
+        assert_eq!(worker.join().unwrap(), "Single job 1 was stopped");
+        assert_eq!(manager.status(id), Some(JobStatus::Stopped));
+    }
+
+    #[test]
+    fn test_panicking_job_is_reported_and_can_run_again() {
+        let manager = JobManager::new();
+        let mut first_run = true;
+        let flaky = Job::new(Box::new(SingleJob::with_task(1, move || {
+            if std::mem::take(&mut first_run) {
+                panic!("corrupt input");
+            }
+            Ok::<_, String>("ok".to_string())
+        })));
+        let id = manager.register(flaky).unwrap();
+
+        assert_eq!(
+            manager.run(id),
+            Err(JobError::Panicked("corrupt input".to_string()))
+        );
+        assert_eq!(manager.status(id), Some(JobStatus::Stopped));
+        // A lock poisoned elsewhere does not make the job look busy or unusable.
+        let job = manager.get(id).unwrap();
+        let _ = thread::spawn(move || {
+            let _held = job.lock().unwrap();
+            panic!("poisoning the lock");
+        })
+        .join();
+        assert_eq!(manager.run(id).unwrap().status, JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_pause_and_resume_keep_the_latest_report() {
+        let manager = Arc::new(JobManager::new());
+        let mut first_run = true;
+        let waiting = Job::new(Box::new(SingleJob::with_context_task(
+            1,
+            move |context: &JobContext| {
+                if std::mem::take(&mut first_run) {
+                    context.cancellation().sleep(Duration::from_secs(30));
+                }
+                context.checkpoint().map_err(|reason| reason.to_string())?;
+                Ok::<_, String>("finished".to_string())
+            },
+        )));
+        let id = manager.register(waiting).unwrap();
+        assert_eq!(manager.report(id), None);
+
+        let worker = {
+            let manager = Arc::clone(&manager);
+            thread::spawn(move || manager.run(id).unwrap())
+        };
+        let started = Instant::now();
+        while manager.status(id) != Some(JobStatus::Running) {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+        manager.pause(id).unwrap();
+        worker.join().unwrap();
+        assert_eq!(manager.status(id), Some(JobStatus::Paused));
+        assert_eq!(
+            manager.report(id).unwrap().output,
+            "Single job 1 was paused"
+        );
+
+        manager.resume(id).unwrap();
+        assert_eq!(manager.status(id), Some(JobStatus::Completed));
+        assert_eq!(
+            manager.report(id).unwrap().output,
+            "Single job 1 completed: finished"
+        );
+        assert_eq!(
+            manager.resume(id),
+            Err(JobError::NotPaused(JobStatus::Completed))
+        );
+    }
+}
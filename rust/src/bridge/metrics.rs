@@ -0,0 +1,371 @@
+use super::JobStatus;
+use super::report::JobReport;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Upper bounds, in seconds, of the duration histogram buckets.
+const BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+// How many of the latest durations of a job the percentiles are taken from.
+const RECENT_DURATIONS: usize = 1000;
+
+#[derive(Debug, Clone, Default)]
+struct Samples {
+    // Keyed by status name so the exposition lists outcomes in a stable order.
+    outcomes: BTreeMap<String, u64>,
+    runs: u64,
+    // Runs per histogram bucket, not cumulative; the last entry is for runs beyond every bound.
+    buckets: [u64; BUCKETS.len() + 1],
+    total: Duration,
+    max: Duration,
+    recent: VecDeque<Duration>,
+    retries: u64,
+    last_started_at: Option<SystemTime>,
+    last_finished_at: Option<SystemTime>,
+}
+
+impl Samples {
+    fn add(&mut self, report: &JobReport) {
+        *self
+            .outcomes
+            .entry(format!("{:?}", report.status))
+            .or_insert(0) += 1;
+        let duration = report.duration();
+        let seconds = duration.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.runs += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+        if self.recent.len() == RECENT_DURATIONS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(duration);
+        self.retries += u64::from(report.retries);
+        self.last_started_at = Some(report.started_at);
+        self.last_finished_at = Some(report.finished_at);
+    }
+
+    fn count(&self, status: JobStatus) -> u64 {
+        let key = format!("{:?}", status);
+        self.outcomes.get(&key).copied().unwrap_or(0)
+    }
+}
+
+// Aggregated figures for every recorded run of one job. The percentiles only cover the
+// latest runs, so they follow changes in how long the job takes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobSummary {
+    pub runs: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub retries: u64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+    pub success_ratio: f64,
+    pub last_started_at: Option<SystemTime>,
+    pub last_finished_at: Option<SystemTime>,
+}
+
+// Collects timings and outcomes from job reports, keyed by job name.
+#[derive(Default)]
+pub struct MetricsRegistry {
+    jobs: Mutex<BTreeMap<String, Samples>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Records the run as `name`, and every job in it that has an id as `name/id`. A job
+    // with an id counts once, however many attempts its report holds.
+    pub fn record(&self, name: &str, report: &JobReport) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.entry(name.to_string()).or_default().add(report);
+        let mut pending: Vec<&JobReport> = match report.id {
+            Some(_) => Vec::new(),
+            None => report.children.iter().collect(),
+        };
+        while let Some(child) = pending.pop() {
+            match child.id {
+                Some(id) => jobs
+                    .entry(format!("{}/{}", name, id))
+                    .or_default()
+                    .add(child),
+                None => pending.extend(&child.children),
+            }
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.jobs.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn summary(&self, name: &str) -> Option<JobSummary> {
+        let jobs = self.jobs.lock().unwrap();
+        let samples = jobs.get(name)?;
+        let mut durations: Vec<Duration> = samples.recent.iter().copied().collect();
+        durations.sort();
+
+        let runs = samples.runs;
+        let successes = samples.count(JobStatus::Completed);
+        Some(JobSummary {
+            runs,
+            successes,
+            failures: samples.count(JobStatus::Failed),
+            retries: samples.retries,
+            p50: percentile(&durations, 0.50),
+            p95: percentile(&durations, 0.95),
+            max: samples.max,
+            success_ratio: if runs == 0 {
+                0.0
+            } else {
+                successes as f64 / runs as f64
+            },
+            last_started_at: samples.last_started_at,
+            last_finished_at: samples.last_finished_at,
+        })
+    }
+
+    // The Prometheus text exposition format, version 0.0.4.
+    pub fn render_prometheus(&self) -> String {
+        let jobs = self.jobs.lock().unwrap();
+        let mut rendered = String::new();
+
+        header(
+            &mut rendered,
+            "job_runs_total",
+            "counter",
+            "Job runs by final status.",
+        );
+        for (name, samples) in jobs.iter() {
+            for (status, count) in &samples.outcomes {
+                let _ = writeln!(
+                    rendered,
+                    "job_runs_total{{job=\"{}\",status=\"{}\"}} {}",
+                    escape(name),
+                    status,
+                    count
+                );
+            }
+        }
+
+        header(
+            &mut rendered,
+            "job_retries_total",
+            "counter",
+            "Retries after a failed attempt.",
+        );
+        for (name, samples) in jobs.iter() {
+            let _ = writeln!(
+                rendered,
+                "job_retries_total{{job=\"{}\"}} {}",
+                escape(name),
+                samples.retries
+            );
+        }
+
+        header(
+            &mut rendered,
+            "job_duration_seconds",
+            "histogram",
+            "Wall-clock duration of job runs.",
+        );
+        for (name, samples) in jobs.iter() {
+            let name = escape(name);
+            let mut count = 0;
+            for (bound, runs) in BUCKETS.iter().zip(samples.buckets) {
+                count += runs;
+                let _ = writeln!(
+                    rendered,
+                    "job_duration_seconds_bucket{{job=\"{}\",le=\"{}\"}} {}",
+                    name, bound, count
+                );
+            }
+            let _ = writeln!(
+                rendered,
+                "job_duration_seconds_bucket{{job=\"{}\",le=\"+Inf\"}} {}",
+                name, samples.runs
+            );
+            let _ = writeln!(
+                rendered,
+                "job_duration_seconds_sum{{job=\"{}\"}} {}",
+                name,
+                samples.total.as_secs_f64()
+            );
+            let _ = writeln!(
+                rendered,
+                "job_duration_seconds_count{{job=\"{}\"}} {}",
+                name, samples.runs
+            );
+        }
+
+        header(
+            &mut rendered,
+            "job_last_finished_timestamp_seconds",
+            "gauge",
+            "When the latest run of the job finished.",
+        );
+        for (name, samples) in jobs.iter() {
+            if let Some(finished_at) = samples.last_finished_at {
+                let _ = writeln!(
+                    rendered,
+                    "job_last_finished_timestamp_seconds{{job=\"{}\"}} {}",
+                    escape(name),
+                    finished_at
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs_f64()
+                );
+            }
+        }
+        rendered
+    }
+
+    // Replaces `path` in one step, so a scraper reading the file never sees half of it.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".tmp");
+        fs::write(&partial, self.render_prometheus())?;
+        fs::rename(&partial, path)
+    }
+}
+
+fn header(rendered: &mut String, metric: &str, kind: &str, help: &str) {
+    let _ = writeln!(rendered, "# HELP {} {}", metric, help);
+    let _ = writeln!(rendered, "# TYPE {} {}", metric, kind);
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Nearest-rank percentile of already sorted durations.
+fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (fraction * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(id: Option<u32>, status: JobStatus, millis: u64) -> JobReport {
+        let mut report = JobReport::new(id, status, "");
+        report.started_at = UNIX_EPOCH + Duration::from_secs(100);
+        report.finished_at = report.started_at + Duration::from_millis(millis);
+        report
+    }
+
+    #[test]
+    fn test_summary_percentiles_and_success_ratio() {
+        let registry = MetricsRegistry::new();
+        for millis in 1..=20 {
+            let status = if millis % 4 == 0 {
+                JobStatus::Failed
+            } else {
+                JobStatus::Completed
+            };
+            registry.record("nightly", &report(None, status, millis));
+        }
+
+        let summary = registry.summary("nightly").unwrap();
+        assert_eq!(summary.runs, 20);
+        assert_eq!(summary.failures, 5);
+        assert_eq!(summary.p50, Duration::from_millis(10));
+        assert_eq!(summary.p95, Duration::from_millis(19));
+        assert_eq!(summary.max, Duration::from_millis(20));
+        assert_eq!(summary.success_ratio, 0.75);
+        assert_eq!(registry.summary("weekly"), None);
+    }
+
+    #[test]
+    fn test_long_histories_keep_bounded_samples() {
+        let registry = MetricsRegistry::new();
+        for run in 0..1500 {
+            let millis = if run < 500 { 1000 } else { 1 };
+            registry.record("hourly", &report(None, JobStatus::Completed, millis));
+        }
+
+        let summary = registry.summary("hourly").unwrap();
+        assert_eq!(summary.runs, 1500);
+        assert_eq!(summary.p95, Duration::from_millis(1));
+        assert_eq!(summary.max, Duration::from_secs(1));
+        assert_eq!(
+            registry.jobs.lock().unwrap()["hourly"].recent.len(),
+            RECENT_DURATIONS
+        );
+        let rendered = registry.render_prometheus();
+        assert!(rendered.contains("job_duration_seconds_bucket{job=\"hourly\",le=\"0.5\"} 1000\n"));
+        assert!(rendered.contains("job_duration_seconds_bucket{job=\"hourly\",le=\"1\"} 1500\n"));
+        assert!(rendered.contains("job_duration_seconds_count{job=\"hourly\"} 1500\n"));
+    }
+
+    #[test]
+    fn test_record_names_jobs_inside_the_tree() {
+        let registry = MetricsRegistry::new();
+        let attempts = vec![
+            report(Some(2), JobStatus::Failed, 1),
+            report(Some(2), JobStatus::Completed, 1),
+        ];
+        let retried = report(Some(2), JobStatus::Completed, 3)
+            .with_children(attempts)
+            .with_retries(1);
+        let inner = report(None, JobStatus::Completed, 3).with_children(vec![retried]);
+        let root = report(None, JobStatus::Completed, 5)
+            .with_children(vec![report(Some(1), JobStatus::Completed, 2), inner]);
+        registry.record("batch", &root);
+
+        assert_eq!(registry.names(), vec!["batch", "batch/1", "batch/2"]);
+        let retried = registry.summary("batch/2").unwrap();
+        assert_eq!((retried.runs, retried.retries), (1, 1));
+    }
+
+    #[test]
+    fn test_prometheus_exposition() {
+        let registry = MetricsRegistry::new();
+        registry.record("say \"hi\"", &report(Some(1), JobStatus::Completed, 20));
+        let rendered = registry.render_prometheus();
+
+        assert!(rendered.starts_with(
+            "# HELP job_runs_total Job runs by final status.\n# TYPE job_runs_total counter\njob_runs_total{job=\"say \\\"hi\\\"\",status=\"Completed\"} 1\n"
+        ));
+        assert!(
+            rendered
+                .contains("job_duration_seconds_bucket{job=\"say \\\"hi\\\"\",le=\"0.01\"} 0\n")
+        );
+        assert!(
+            rendered
+                .contains("job_duration_seconds_bucket{job=\"say \\\"hi\\\"\",le=\"0.025\"} 1\n")
+        );
+        assert!(rendered.contains("job_duration_seconds_count{job=\"say \\\"hi\\\"\"} 1\n"));
+        assert!(
+            rendered
+                .contains("job_last_finished_timestamp_seconds{job=\"say \\\"hi\\\"\"} 100.02\n")
+        );
+
+        let path = std::env::temp_dir().join(format!("metrics-{}.prom", std::process::id()));
+        registry.write_to(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), rendered);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,207 @@
+use super::JobStatus;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+
+// Something that happened to a job in a running tree. `path` locates the job the same
+// way `JobContext` does: the child index taken at each level below the root.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobEvent {
+    // Only the root knows the status it left; jobs below it report where they moved to.
+    StatusChanged {
+        path: Vec<usize>,
+        from: Option<JobStatus>,
+        to: JobStatus,
+    },
+    // `percent` is between 0 and 100. Groups report the average of their children.
+    Progress {
+        path: Vec<usize>,
+        percent: f64,
+        message: String,
+    },
+}
+
+type Subscriber = Box<dyn Fn(&JobEvent) + Send>;
+
+// The callbacks listening to one job tree. Clones share the same list, so a subscriber
+// added through any clone sees every event.
+#[derive(Clone, Default)]
+pub struct Subscribers {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl Subscribers {
+    pub fn subscribe<F>(&self, subscriber: F)
+    where
+        F: Fn(&JobEvent) + Send + 'static,
+    {
+        self.subscribers.lock().unwrap().push(Box::new(subscriber));
+    }
+
+    // Events arrive on the receiver in the order they were published. Once the receiver
+    // is dropped, events for it are discarded.
+    pub fn channel(&self) -> Receiver<JobEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribe(move |event| {
+            let _ = sender.send(event.clone());
+        });
+        receiver
+    }
+
+    pub fn publish(&self, event: &JobEvent) {
+        for subscriber in self.subscribers.lock().unwrap().iter() {
+            subscriber(event);
+        }
+    }
+}
+
+// The latest progress of every child of one running group. Each update republishes the
+// group's average and passes it on to the group above, so the root tracks the whole tree.
+pub struct GroupProgress {
+    path: Vec<usize>,
+    percents: Mutex<Vec<f64>>,
+    parent: Option<Arc<GroupProgress>>,
+    subscribers: Subscribers,
+}
+
+impl GroupProgress {
+    pub fn new(
+        path: Vec<usize>,
+        percents: Vec<f64>,
+        parent: Option<Arc<GroupProgress>>,
+        subscribers: Subscribers,
+    ) -> Self {
+        GroupProgress {
+            path,
+            percents: Mutex::new(percents),
+            parent,
+            subscribers,
+        }
+    }
+
+    pub fn update(&self, index: usize, percent: f64) {
+        let (average, complete, total) = {
+            let mut percents = self.percents.lock().unwrap();
+            percents[index] = percent.clamp(0.0, 100.0);
+            let complete = percents.iter().filter(|percent| **percent >= 100.0).count();
+            let average = percents.iter().sum::<f64>() / percents.len() as f64;
+            (average, complete, percents.len())
+        };
+
+        self.subscribers.publish(&JobEvent::Progress {
+            path: self.path.clone(),
+            percent: average,
+            message: format!("{} of {} jobs complete", complete, total),
+        });
+        if let (Some(parent), Some(index)) = (&self.parent, self.path.last()) {
+            parent.update(*index, average);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{Job, JobContext, JobImpl, MultipleJob, SingleJob};
+
+    fn halfway(id: u32) -> Box<dyn JobImpl> {
+        Box::new(SingleJob::with_context_task(id, |context: &JobContext| {
+            context.report_progress(50.0, "halfway");
+            Ok::<_, String>("done".to_string())
+        }))
+    }
+
+    fn progress_of(events: &[JobEvent], wanted: &[usize]) -> Vec<f64> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                JobEvent::Progress { path, percent, .. } if path == wanted => Some(*percent),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_group_progress_averages_children() {
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![halfway(1), halfway(2)])));
+        let events = job.subscribers().channel();
+        job.run().unwrap();
+
+        let events: Vec<JobEvent> = events.try_iter().collect();
+        assert_eq!(progress_of(&events, &[]), vec![25.0, 50.0, 75.0, 100.0]);
+        assert_eq!(progress_of(&events, &[1]), vec![50.0]);
+        assert!(events.contains(&JobEvent::Progress {
+            path: vec![],
+            percent: 100.0,
+            message: "2 of 2 jobs complete".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_nested_group_progress_reaches_the_root() {
+        let inner = MultipleJob::new(vec![halfway(1), halfway(2)]);
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![
+            Box::new(inner),
+            halfway(3),
+        ])));
+        let events = job.subscribers().channel();
+        job.run().unwrap();
+
+        let events: Vec<JobEvent> = events.try_iter().collect();
+        assert_eq!(progress_of(&events, &[0]), vec![25.0, 50.0, 75.0, 100.0]);
+        assert_eq!(
+            progress_of(&events, &[]),
+            vec![12.5, 25.0, 37.5, 50.0, 50.0, 75.0, 100.0]
+        );
+    }
+
+    #[test]
+    fn test_subscribers_see_status_transitions() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![
+            Box::new(SingleJob::with_task(1, || {
+                Ok::<_, String>("ok".to_string())
+            })),
+            Box::new(SingleJob::with_task(2, || Err("boom"))),
+        ])));
+        let log = Arc::clone(&seen);
+        job.subscribers().subscribe(move |event| {
+            if let JobEvent::StatusChanged { path, from, to } = event {
+                log.lock().unwrap().push((path.clone(), *from, *to));
+            }
+        });
+        job.run().unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (vec![], Some(JobStatus::Pending), JobStatus::Running),
+                (vec![0], None, JobStatus::Running),
+                (vec![0], None, JobStatus::Completed),
+                (vec![1], None, JobStatus::Running),
+                (vec![1], None, JobStatus::Failed),
+                (vec![], Some(JobStatus::Running), JobStatus::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unchanged_status_is_not_published_again() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut job = Job::new(Box::new(MultipleJob::new(vec![Box::new(SingleJob::new(
+            1,
+        ))])));
+        let log = Arc::clone(&seen);
+        job.subscribers().subscribe(move |event| {
+            if let JobEvent::StatusChanged { path, to, .. } = event {
+                log.lock().unwrap().push((path.clone(), *to));
+            }
+        });
+        job.run().unwrap();
+
+        // A job without a task is still running when its run returns.
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![(vec![], JobStatus::Running), (vec![0], JobStatus::Running)]
+        );
+    }
+}
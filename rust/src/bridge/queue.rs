@@ -0,0 +1,385 @@
+use super::cancel::CancellationToken;
+use super::clock::{Clock, SystemClock};
+use super::rate::RateLimiter;
+use super::report::JobReport;
+use super::{Job, JobError};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// How long jobs of one priority waited between being submitted and being picked up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WaitStats {
+    pub count: usize,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl WaitStats {
+    pub fn average(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total / count as u32,
+        }
+    }
+
+    fn record(&mut self, waited: Duration) {
+        self.count += 1;
+        self.total += waited;
+        self.max = self.max.max(waited);
+    }
+}
+
+struct Queued {
+    priority: u8,
+    // Submission order, which breaks ties between jobs of the same effective priority.
+    sequence: u64,
+    // A monotonic reading of the queue's clock.
+    submitted_at: Duration,
+    job: Job,
+    result: Sender<Result<JobReport, JobError>>,
+}
+
+struct QueueState {
+    waiting: Vec<Queued>,
+    next_sequence: u64,
+    in_flight: usize,
+    waits: BTreeMap<u8, WaitStats>,
+    // With aging, a job gains one priority level for every interval it spends waiting.
+    aging: Option<Duration>,
+    // Measures waiting times, for aging and for `waits`.
+    clock: Arc<dyn Clock>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    closed: bool,
+}
+
+impl QueueState {
+    fn effective_priority(&self, queued: &Queued, now: Duration) -> u8 {
+        let Some(interval) = self.aging else {
+            return queued.priority;
+        };
+        let waited = now.saturating_sub(queued.submitted_at);
+        let levels = waited.as_nanos() / interval.as_nanos().max(1);
+        queued
+            .priority
+            .saturating_add(levels.min(u8::MAX as u128) as u8)
+    }
+
+    // The highest effective priority wins; within a priority, the oldest job does.
+    fn take_next(&mut self, now: Duration) -> Option<Queued> {
+        let index = (0..self.waiting.len()).max_by(|a, b| {
+            let (a, b) = (&self.waiting[*a], &self.waiting[*b]);
+            self.effective_priority(a, now)
+                .cmp(&self.effective_priority(b, now))
+                .then(b.sequence.cmp(&a.sequence))
+        })?;
+        Some(self.waiting.remove(index))
+    }
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    available: Condvar,
+}
+
+// The result of a submitted job, available once a worker has run it.
+pub struct Ticket {
+    result: Receiver<Result<JobReport, JobError>>,
+}
+
+impl Ticket {
+    pub fn wait(self) -> Result<JobReport, JobError> {
+        self.result.recv().unwrap_or_else(|_| {
+            Err(JobError::Panicked(
+                "the worker exited without running the job".to_string(),
+            ))
+        })
+    }
+}
+
+// Runs submitted jobs on a fixed pool of worker threads, most urgent first. Higher
+// numbers are more urgent. Dropping the queue runs whatever is still waiting, then
+// joins the workers.
+pub struct JobQueue {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl JobQueue {
+    pub fn new(workers: usize) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState {
+                waiting: Vec::new(),
+                next_sequence: 0,
+                in_flight: 0,
+                waits: BTreeMap::new(),
+                aging: None,
+                clock: Arc::new(SystemClock),
+                rate_limiter: None,
+                closed: false,
+            }),
+            available: Condvar::new(),
+        });
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || work(&shared))
+            })
+            .collect();
+        JobQueue { shared, workers }
+    }
+
+    // Prevents starvation: a job waiting for `interval` is treated as one level more urgent.
+    pub fn with_aging(self, interval: Duration) -> Self {
+        self.shared.state.lock().unwrap().aging = Some(interval);
+        self
+    }
+
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        self.shared.state.lock().unwrap().clock = clock;
+        self
+    }
+
+    // Workers wait for the limiter before starting each job, on the queue's clock. The
+    // time spent there counts towards the job's wait.
+    pub fn with_rate_limiter(self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.shared.state.lock().unwrap().rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn submit(&self, priority: u8, job: Job) -> Ticket {
+        let (sender, receiver) = mpsc::channel();
+        let mut state = self.shared.state.lock().unwrap();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        let submitted_at = state.clock.monotonic();
+        state.waiting.push(Queued {
+            priority,
+            sequence,
+            submitted_at,
+            job,
+            result: sender,
+        });
+        self.shared.available.notify_one();
+        Ticket { result: receiver }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.shared.state.lock().unwrap().waiting.len()
+    }
+
+    pub fn depth_by_priority(&self) -> BTreeMap<u8, usize> {
+        let mut depths = BTreeMap::new();
+        for queued in &self.shared.state.lock().unwrap().waiting {
+            *depths.entry(queued.priority).or_insert(0) += 1;
+        }
+        depths
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.shared.state.lock().unwrap().in_flight
+    }
+
+    // Keyed by the priority jobs were submitted with, not the one aging raised them to.
+    pub fn waits(&self) -> BTreeMap<u8, WaitStats> {
+        self.shared.state.lock().unwrap().waits.clone()
+    }
+}
+
+impl Drop for JobQueue {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let (mut queued, clock, rate_limiter) = {
+            let mut state = shared.state.lock().unwrap();
+            let queued = loop {
+                let now = state.clock.monotonic();
+                if let Some(queued) = state.take_next(now) {
+                    break queued;
+                }
+                if state.closed {
+                    return;
+                }
+                state = shared.available.wait(state).unwrap();
+            };
+            state.in_flight += 1;
+            (queued, Arc::clone(&state.clock), state.rate_limiter.clone())
+        };
+
+        // Nothing cancels the wait, so the limiter always lets the job through eventually.
+        if let Some(limiter) = rate_limiter {
+            let _ = limiter.acquire(&CancellationToken::with_clock(Arc::clone(&clock)));
+        }
+        let waited = clock.monotonic().saturating_sub(queued.submitted_at);
+        shared
+            .state
+            .lock()
+            .unwrap()
+            .waits
+            .entry(queued.priority)
+            .or_default()
+            .record(waited);
+
+        // A panicking job fails its own ticket, not the worker that ran it.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| queued.job.run()))
+            .unwrap_or_else(|payload| Err(JobError::Panicked(panic_message(payload.as_ref()))));
+        shared.state.lock().unwrap().in_flight -= 1;
+        // The submitter may have dropped its ticket; the job still counts as done.
+        let _ = queued.result.send(result);
+    }
+}
+
+pub(super) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::SingleJob;
+    use crate::bridge::clock::ManualClock;
+    use std::time::Instant;
+
+    fn recording(id: u32, log: &Arc<Mutex<Vec<u32>>>) -> Job {
+        let log = Arc::clone(log);
+        Job::new(Box::new(SingleJob::with_task(id, move || {
+            log.lock().unwrap().push(id);
+            Ok::<_, String>("ok".to_string())
+        })))
+    }
+
+    // Occupies the queue's only worker until the returned sender is dropped, so the jobs
+    // submitted in the meantime all compete for the next free slot.
+    fn block_worker(queue: &JobQueue) -> (Sender<()>, Ticket) {
+        let (release, released) = mpsc::channel::<()>();
+        let released = Mutex::new(released);
+        let gate = Job::new(Box::new(SingleJob::with_task(0, move || {
+            let _ = released.lock().unwrap().recv();
+            Ok::<_, String>("released".to_string())
+        })));
+        let ticket = queue.submit(u8::MAX, gate);
+        while queue.in_flight() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        (release, ticket)
+    }
+
+    #[test]
+    fn test_higher_priority_first_and_fifo_within_priority() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let queue = JobQueue::new(1);
+        let (release, gate) = block_worker(&queue);
+
+        let tickets: Vec<Ticket> = [(1, 1), (2, 5), (3, 1), (4, 5), (5, 3)]
+            .into_iter()
+            .map(|(id, priority)| queue.submit(priority, recording(id, &log)))
+            .collect();
+        assert_eq!(queue.depth(), 5);
+        assert_eq!(
+            queue.depth_by_priority(),
+            BTreeMap::from([(1, 2), (3, 1), (5, 2)])
+        );
+
+        drop(release);
+        gate.wait().unwrap();
+        for ticket in tickets {
+            ticket.wait().unwrap();
+        }
+        assert_eq!(*log.lock().unwrap(), vec![2, 4, 5, 1, 3]);
+        let waits = queue.waits();
+        assert_eq!(waits[&1].count, 2);
+        assert!(waits[&1].average() >= waits[&5].average());
+    }
+
+    #[test]
+    fn test_aging_lets_old_jobs_overtake_newer_urgent_ones() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let clock = Arc::new(ManualClock::new(std::time::UNIX_EPOCH));
+        let queue = JobQueue::new(1)
+            .with_aging(Duration::from_millis(10))
+            .with_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+        let (release, _gate) = block_worker(&queue);
+
+        let old = queue.submit(0, recording(1, &log));
+        clock.advance(Duration::from_millis(60));
+        let urgent = queue.submit(2, recording(2, &log));
+
+        drop(release);
+        old.wait().unwrap();
+        urgent.wait().unwrap();
+        assert_eq!(*log.lock().unwrap(), vec![1, 2]);
+        assert_eq!(queue.waits()[&0].max, Duration::from_millis(60));
+    }
+
+    #[test]
+    fn test_panicking_job_fails_its_ticket_and_keeps_the_worker() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let queue = JobQueue::new(1);
+        let panicking = Job::new(Box::new(SingleJob::with_task(
+            1,
+            || -> Result<String, String> { panic!("disk on fire") },
+        )));
+
+        assert_eq!(
+            queue.submit(1, panicking).wait(),
+            Err(JobError::Panicked("disk on fire".to_string()))
+        );
+        assert!(queue.submit(1, recording(2, &log)).wait().is_ok());
+        assert_eq!(*log.lock().unwrap(), vec![2]);
+        assert_eq!(queue.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_workers_run_jobs_in_parallel() {
+        let queue = JobQueue::new(3);
+        let (release, released) = mpsc::channel::<()>();
+        let released = Arc::new(Mutex::new(released));
+        let tickets: Vec<Ticket> = (1..=3)
+            .map(|id| {
+                let released = Arc::clone(&released);
+                queue.submit(
+                    1,
+                    Job::new(Box::new(SingleJob::with_task(id, move || {
+                        // Holds each worker until every job has started.
+                        let _ = released
+                            .lock()
+                            .unwrap()
+                            .recv_timeout(Duration::from_secs(5));
+                        Ok::<_, String>("ok".to_string())
+                    }))),
+                )
+            })
+            .collect();
+
+        let started = Instant::now();
+        while queue.in_flight() < 3 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(queue.depth(), 0);
+        drop(release);
+        for ticket in tickets {
+            assert_eq!(
+                ticket.wait().unwrap().status,
+                crate::bridge::JobStatus::Completed
+            );
+        }
+    }
+}
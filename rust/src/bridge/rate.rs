@@ -0,0 +1,267 @@
+use super::cancel::{CancelReason, CancellationToken};
+use super::clock::{Clock, SystemClock};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+enum Limit {
+    // Holds up to `burst` permits and gains `rate` of them every `per`.
+    TokenBucket {
+        rate: u32,
+        per: Duration,
+        burst: u32,
+        tokens: f64,
+        refilled_at: SystemTime,
+    },
+    // At most `limit` permits within any span of `window`.
+    SlidingWindow {
+        limit: u32,
+        window: Duration,
+        granted: VecDeque<SystemTime>,
+    },
+}
+
+impl Limit {
+    // Takes a permit if one is free at `now`, or says how long until one will be.
+    fn take(&mut self, now: SystemTime) -> Result<(), Duration> {
+        let wait = self.wait(now);
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        match self {
+            Limit::TokenBucket { tokens, .. } => *tokens -= 1.0,
+            Limit::SlidingWindow { granted, .. } => granted.push_back(now),
+        }
+        Ok(())
+    }
+
+    fn wait(&mut self, now: SystemTime) -> Duration {
+        match self {
+            Limit::TokenBucket {
+                rate,
+                per,
+                burst,
+                tokens,
+                refilled_at,
+            } => {
+                let elapsed = now.duration_since(*refilled_at).unwrap_or_default();
+                let per_permit = per.as_secs_f64() / f64::from(*rate);
+                *tokens = (*tokens + elapsed.as_secs_f64() / per_permit).min(f64::from(*burst));
+                *refilled_at = now;
+                if *tokens >= 1.0 {
+                    Duration::ZERO
+                } else {
+                    Duration::from_secs_f64((1.0 - *tokens) * per_permit)
+                }
+            }
+            Limit::SlidingWindow {
+                limit,
+                window,
+                granted,
+            } => {
+                while granted
+                    .front()
+                    .is_some_and(|at| now.duration_since(*at).unwrap_or_default() >= *window)
+                {
+                    granted.pop_front();
+                }
+                if granted.len() < *limit as usize {
+                    return Duration::ZERO;
+                }
+                let oldest = granted[granted.len() - *limit as usize];
+                (oldest + *window).duration_since(now).unwrap_or_default()
+            }
+        }
+    }
+}
+
+// Spaces out job starts, for instance to stay under a downstream quota. One limiter can
+// be shared by several groups and queues, which then count against the same limit.
+pub struct RateLimiter {
+    limit: Mutex<Limit>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimiter {
+    // Allows `rate` starts every `per` on average, and a burst of as many at once.
+    pub fn token_bucket(rate: u32, per: Duration) -> Self {
+        let rate = rate.max(1);
+        Self::with_limit(Limit::TokenBucket {
+            rate,
+            per,
+            burst: rate,
+            tokens: f64::from(rate),
+            refilled_at: SystemTime::now(),
+        })
+    }
+
+    // Allows at most `limit` starts within any `window`.
+    pub fn sliding_window(limit: u32, window: Duration) -> Self {
+        Self::with_limit(Limit::SlidingWindow {
+            limit: limit.max(1),
+            window,
+            granted: VecDeque::new(),
+        })
+    }
+
+    fn with_limit(limit: Limit) -> Self {
+        RateLimiter {
+            limit: Mutex::new(limit),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // Only meaningful for a token bucket: how many starts may happen back to back.
+    pub fn with_burst(self, burst: u32) -> Self {
+        if let Limit::TokenBucket {
+            burst: limit,
+            tokens,
+            ..
+        } = &mut *self.limit.lock().unwrap()
+        {
+            *limit = burst.max(1);
+            *tokens = f64::from(*limit);
+        }
+        self
+    }
+
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        if let Limit::TokenBucket { refilled_at, .. } = &mut *self.limit.lock().unwrap() {
+            *refilled_at = clock.now();
+        }
+        RateLimiter { clock, ..self }
+    }
+
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.limit.lock().unwrap().take(self.clock.now())
+    }
+
+    // How long a start asked for now would wait. Other waiters may get there first.
+    pub fn wait_estimate(&self) -> Duration {
+        self.limit.lock().unwrap().wait(self.clock.now())
+    }
+
+    // Waits on `cancellation`'s clock, which should be the limiter's own, until a start
+    // is allowed. Fails if the wait is cancelled.
+    pub fn acquire(&self, cancellation: &CancellationToken) -> Result<(), CancelReason> {
+        loop {
+            match self.try_acquire() {
+                Ok(()) => return Ok(()),
+                Err(wait) => {
+                    if !cancellation.sleep(wait) {
+                        return Err(cancellation.reason().unwrap_or(CancelReason::Stopped));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::clock::ManualClock;
+    use crate::bridge::harness::JobHarness;
+    use crate::bridge::queue::JobQueue;
+    use crate::bridge::{Job, JobImpl, JobStatus, MultipleJob, SingleJob};
+    use JobStatus::Running;
+    use std::time::{Instant, UNIX_EPOCH};
+
+    fn manual_clock() -> Arc<ManualClock> {
+        Arc::new(ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_000)))
+    }
+
+    #[test]
+    fn test_token_bucket_refills_gradually() {
+        let clock = manual_clock();
+        let limiter = RateLimiter::token_bucket(10, Duration::from_secs(1))
+            .with_burst(2)
+            .with_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+
+        assert_eq!(limiter.try_acquire(), Ok(()));
+        assert_eq!(limiter.try_acquire(), Ok(()));
+        assert_eq!(limiter.try_acquire(), Err(Duration::from_millis(100)));
+
+        clock.advance(Duration::from_millis(40));
+        assert_eq!(limiter.wait_estimate(), Duration::from_millis(60));
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(limiter.try_acquire(), Ok(()));
+        // A long idle spell refills no further than the burst.
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(limiter.try_acquire(), Ok(()));
+        assert_eq!(limiter.try_acquire(), Ok(()));
+        assert!(limiter.try_acquire().is_err());
+    }
+
+    fn quick(id: u32) -> Box<dyn JobImpl> {
+        Box::new(SingleJob::with_task(id, || {
+            Ok::<_, String>("ok".to_string())
+        }))
+    }
+
+    #[test]
+    fn test_sliding_window_spaces_out_group_starts() {
+        let clock = manual_clock();
+        let limiter = Arc::new(
+            RateLimiter::sliding_window(2, Duration::from_secs(1))
+                .with_clock(Arc::clone(&clock) as Arc<dyn Clock>),
+        );
+        let group =
+            MultipleJob::new((1..=5).map(quick).collect()).with_rate_limiter(Arc::clone(&limiter));
+        let mut harness = JobHarness::with_clock(Box::new(group), clock);
+        harness.run();
+
+        let starts: Vec<(Vec<usize>, Duration)> = harness
+            .transitions()
+            .into_iter()
+            .filter(|transition| !transition.path.is_empty() && transition.status == Running)
+            .map(|transition| (transition.path, transition.at))
+            .collect();
+        let at = Duration::from_secs;
+        assert_eq!(
+            starts,
+            vec![
+                (vec![0], at(0)),
+                (vec![1], at(0)),
+                (vec![2], at(1)),
+                (vec![3], at(1)),
+                (vec![4], at(2)),
+            ]
+        );
+        // The last start left room for one more in its window.
+        assert_eq!(limiter.wait_estimate(), Duration::ZERO);
+        limiter.try_acquire().unwrap();
+        assert_eq!(limiter.wait_estimate(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_limiter_is_shared_by_parallel_workers_and_queues() {
+        // Real time here: parallel sleepers would each move a simulated clock.
+        let limiter = Arc::new(RateLimiter::token_bucket(1, Duration::from_millis(30)));
+        let group = MultipleJob::new((1..=4).map(quick).collect())
+            .with_max_concurrency(4)
+            .with_rate_limiter(Arc::clone(&limiter));
+        let started = Instant::now();
+        assert_eq!(
+            Job::new(Box::new(group)).run().unwrap().status,
+            JobStatus::Completed
+        );
+        assert!(started.elapsed() >= Duration::from_millis(90));
+
+        let clock = manual_clock();
+        let limiter = Arc::new(
+            RateLimiter::sliding_window(1, Duration::from_secs(1))
+                .with_clock(Arc::clone(&clock) as Arc<dyn Clock>),
+        );
+        let queue = JobQueue::new(1)
+            .with_clock(Arc::clone(&clock) as Arc<dyn Clock>)
+            .with_rate_limiter(limiter);
+        let tickets: Vec<_> = (1..=3)
+            .map(|id| queue.submit(1, Job::new(quick(id))))
+            .collect();
+        for ticket in tickets {
+            ticket.wait().unwrap();
+        }
+        assert_eq!(queue.waits()[&1].max, Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,277 @@
+use super::JobStatus;
+use std::fmt::{self, Display, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    // The flat, line-per-job text that `Job::run` used to return.
+    Plain,
+    // One line per job, indented by depth, with status and duration.
+    Tree,
+    Json,
+}
+
+// The outcome of running or stopping one job. Groups nest their children's reports,
+// so a report mirrors the shape of the job tree that produced it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobReport {
+    pub id: Option<u32>,
+    pub status: JobStatus,
+    pub started_at: SystemTime,
+    pub finished_at: SystemTime,
+    pub output: String,
+    pub children: Vec<JobReport>,
+    // Reports of the compensating actions a failed group ran to undo its completed
+    // children, latest child first.
+    pub compensations: Vec<JobReport>,
+    // Summary lines a group adds after its children, such as failure-policy totals.
+    pub notes: Vec<String>,
+    // How many times the job was run again after failing, for jobs that retry.
+    pub retries: u32,
+}
+
+impl JobReport {
+    pub fn new(id: Option<u32>, status: JobStatus, output: impl Into<String>) -> Self {
+        let now = SystemTime::now();
+        JobReport {
+            id,
+            status,
+            started_at: now,
+            finished_at: now,
+            output: output.into(),
+            children: Vec::new(),
+            compensations: Vec::new(),
+            notes: Vec::new(),
+            retries: 0,
+        }
+    }
+
+    // When the run started and ended, by the clock of the context it ran in.
+    pub fn with_span(mut self, started_at: SystemTime, finished_at: SystemTime) -> Self {
+        self.started_at = started_at;
+        self.finished_at = finished_at;
+        self
+    }
+
+    pub fn with_children(mut self, children: Vec<JobReport>) -> Self {
+        self.children = children;
+        self
+    }
+
+    pub fn with_compensations(mut self, compensations: Vec<JobReport>) -> Self {
+        self.compensations = compensations;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.finished_at
+            .duration_since(self.started_at)
+            .unwrap_or_default()
+    }
+
+    // Compensating actions anywhere in the tree that failed, leaving their job's effects
+    // in place.
+    pub fn compensation_failures(&self) -> Vec<&JobReport> {
+        let mut failures = Vec::new();
+        let mut pending: Vec<&JobReport> = vec![self];
+        while let Some(report) = pending.pop() {
+            pending.extend(&report.children);
+            for compensation in &report.compensations {
+                let mut undo: Vec<&JobReport> = vec![compensation];
+                while let Some(step) = undo.pop() {
+                    if step.id.is_some() && step.status == JobStatus::Failed {
+                        failures.push(step);
+                    }
+                    undo.extend(&step.children);
+                }
+            }
+        }
+        failures
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        let mut rendered = String::new();
+        match format {
+            ReportFormat::Plain => self.render_plain(&mut rendered),
+            ReportFormat::Tree => self.render_tree(&mut rendered, 0, ""),
+            ReportFormat::Json => self.render_json(&mut rendered),
+        }
+        rendered
+    }
+
+    fn render_plain(&self, rendered: &mut String) {
+        push_line(rendered, &self.output);
+        for child in self.children.iter().chain(&self.compensations) {
+            child.render_plain(rendered);
+        }
+        for note in &self.notes {
+            push_line(rendered, note);
+        }
+    }
+
+    fn render_tree(&self, rendered: &mut String, depth: usize, prefix: &str) {
+        let indent = "  ".repeat(depth);
+        let label = match self.id {
+            Some(id) => format!("job {}", id),
+            None => "group".to_string(),
+        };
+        push_line(
+            rendered,
+            &format!(
+                "{}- {}{} [{:?}, {:?}] {}",
+                indent,
+                prefix,
+                label,
+                self.status,
+                self.duration(),
+                self.output
+            ),
+        );
+        for child in &self.children {
+            child.render_tree(rendered, depth + 1, prefix);
+        }
+        for compensation in &self.compensations {
+            compensation.render_tree(rendered, depth + 1, "compensation ");
+        }
+        for note in &self.notes {
+            push_line(rendered, &format!("{}  note: {}", indent, note));
+        }
+    }
+
+    fn render_json(&self, rendered: &mut String) {
+        rendered.push('{');
+        match self.id {
+            Some(id) => write!(rendered, "\"id\":{},", id).unwrap(),
+            None => rendered.push_str("\"id\":null,"),
+        }
+        write!(
+            rendered,
+            "\"status\":\"{:?}\",\"started_at_ms\":{},\"finished_at_ms\":{},\"duration_ms\":{},\"output\":",
+            self.status,
+            epoch_millis(self.started_at),
+            epoch_millis(self.finished_at),
+            self.duration().as_millis()
+        )
+        .unwrap();
+        push_json_string(rendered, &self.output);
+
+        rendered.push_str(",\"children\":[");
+        for (index, child) in self.children.iter().enumerate() {
+            if index > 0 {
+                rendered.push(',');
+            }
+            child.render_json(rendered);
+        }
+        rendered.push_str("],\"compensations\":[");
+        for (index, compensation) in self.compensations.iter().enumerate() {
+            if index > 0 {
+                rendered.push(',');
+            }
+            compensation.render_json(rendered);
+        }
+        rendered.push_str("],\"notes\":[");
+        for (index, note) in self.notes.iter().enumerate() {
+            if index > 0 {
+                rendered.push(',');
+            }
+            push_json_string(rendered, note);
+        }
+        rendered.push_str("]}");
+    }
+}
+
+impl Display for JobReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(ReportFormat::Plain))
+    }
+}
+
+fn push_line(rendered: &mut String, line: &str) {
+    if !rendered.is_empty() {
+        rendered.push('\n');
+    }
+    rendered.push_str(line);
+}
+
+fn epoch_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+fn push_json_string(rendered: &mut String, value: &str) {
+    rendered.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => rendered.push_str("\\\""),
+            '\\' => rendered.push_str("\\\\"),
+            '\n' => rendered.push_str("\\n"),
+            '\r' => rendered.push_str("\\r"),
+            '\t' => rendered.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(rendered, "\\u{:04x}", c as u32).unwrap(),
+            c => rendered.push(c),
+        }
+    }
+    rendered.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    fn sample() -> JobReport {
+        let mut first = JobReport::new(Some(1), JobStatus::Completed, "Single job 1 completed: ok");
+        first.started_at = at(1_000);
+        first.finished_at = at(1_005);
+        let mut second =
+            JobReport::new(Some(2), JobStatus::Failed, "Single job 2 failed: \"boom\"");
+        second.started_at = at(1_005);
+        second.finished_at = at(1_020);
+
+        let mut group = JobReport::new(None, JobStatus::Failed, "Running multiple jobs:")
+            .with_children(vec![first, second])
+            .with_note("1 of 2 jobs failed");
+        group.started_at = at(1_000);
+        group.finished_at = at(1_020);
+        group
+    }
+
+    #[test]
+    fn test_plain_report_matches_legacy_text() {
+        assert_eq!(
+            sample().to_string(),
+            "Running multiple jobs:\nSingle job 1 completed: ok\nSingle job 2 failed: \"boom\"\n1 of 2 jobs failed"
+        );
+    }
+
+    #[test]
+    fn test_tree_report_indents_children() {
+        let expected = "- group [Failed, 20ms] Running multiple jobs:\n  - job 1 [Completed, 5ms] Single job 1 completed: ok\n  - job 2 [Failed, 15ms] Single job 2 failed: \"boom\"\n  note: 1 of 2 jobs failed";
+        assert_eq!(sample().render(ReportFormat::Tree), expected);
+    }
+
+    #[test]
+    fn test_json_report_nests_children() {
+        let expected = concat!(
+            "{\"id\":null,\"status\":\"Failed\",\"started_at_ms\":1000,\"finished_at_ms\":1020,\"duration_ms\":20,\"output\":\"Running multiple jobs:\",\"children\":[",
+            "{\"id\":1,\"status\":\"Completed\",\"started_at_ms\":1000,\"finished_at_ms\":1005,\"duration_ms\":5,\"output\":\"Single job 1 completed: ok\",\"children\":[],\"compensations\":[],\"notes\":[]},",
+            "{\"id\":2,\"status\":\"Failed\",\"started_at_ms\":1005,\"finished_at_ms\":1020,\"duration_ms\":15,\"output\":\"Single job 2 failed: \\\"boom\\\"\",\"children\":[],\"compensations\":[],\"notes\":[]}",
+            "],\"compensations\":[],\"notes\":[\"1 of 2 jobs failed\"]}"
+        );
+        assert_eq!(sample().render(ReportFormat::Json), expected);
+    }
+}
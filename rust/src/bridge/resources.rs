@@ -0,0 +1,365 @@
+use super::cancel::CancelReason;
+use super::report::JobReport;
+use super::{JobContext, JobImpl, JobOutline, JobStatus};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+// How often a job waiting for resources checks whether it was cancelled.
+const WAIT_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceError {
+    // Taking `requested` while holding `held` reverses an order seen before; `cycle` is
+    // the chain of acquisitions that could block each other.
+    WouldDeadlock {
+        held: String,
+        requested: String,
+        cycle: Vec<String>,
+    },
+    Cancelled(CancelReason),
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResourceError::WouldDeadlock {
+                held,
+                requested,
+                cycle,
+            } => write!(
+                f,
+                "taking '{}' while holding '{}' could deadlock: {}",
+                requested,
+                held,
+                cycle.join(" -> ")
+            ),
+            ResourceError::Cancelled(reason) => write!(f, "{} while waiting", reason),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    limits: BTreeMap<String, usize>,
+    in_use: BTreeMap<String, usize>,
+    // An edge from `a` to `b` means some job took `b` while holding `a`.
+    order: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl PoolState {
+    fn limit(&self, name: &str) -> usize {
+        self.limits.get(name).copied().unwrap_or(1)
+    }
+
+    fn available(&self, names: &[String]) -> bool {
+        names
+            .iter()
+            .all(|name| self.in_use.get(name).copied().unwrap_or(0) < self.limit(name))
+    }
+
+    // A path of earlier acquisitions from `from` to `to`, if there is one.
+    fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![vec![from.to_string()]];
+        while let Some(path) = pending.pop() {
+            let last = path.last().unwrap();
+            if last == to {
+                return Some(path);
+            }
+            if !visited.insert(last.clone()) {
+                continue;
+            }
+            for next in self.order.get(last).into_iter().flatten() {
+                let mut longer = path.clone();
+                longer.push(next.clone());
+                pending.push(longer);
+            }
+        }
+        None
+    }
+}
+
+struct Shared {
+    state: Mutex<PoolState>,
+    released: Condvar,
+}
+
+// Named resources shared by the jobs of one or more trees. A resource is exclusive
+// unless it is given a limit, which turns it into a concurrency group.
+#[derive(Clone)]
+pub struct ResourcePool {
+    shared: Arc<Shared>,
+}
+
+impl ResourcePool {
+    pub fn new() -> Self {
+        ResourcePool {
+            shared: Arc::new(Shared {
+                state: Mutex::new(PoolState::default()),
+                released: Condvar::new(),
+            }),
+        }
+    }
+
+    pub fn with_limit(self, name: impl Into<String>, limit: usize) -> Self {
+        self.shared
+            .state
+            .lock()
+            .unwrap()
+            .limits
+            .insert(name.into(), limit.max(1));
+        self
+    }
+
+    pub fn in_use(&self, name: &str) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.in_use.get(name).copied().unwrap_or(0)
+    }
+
+    // Takes every resource in `names` at once, so a job never holds some of its resources
+    // while waiting for the rest. Only nested jobs, which take resources while an
+    // ancestor holds others, can deadlock; those orders are checked before waiting.
+    fn acquire(
+        &self,
+        names: &[String],
+        held: &[String],
+        context: &JobContext,
+    ) -> Result<Lease, ResourceError> {
+        let mut state = self.shared.state.lock().unwrap();
+        for requested in names {
+            for holding in held {
+                if let Some(mut cycle) = state.path(requested, holding) {
+                    cycle.push(requested.clone());
+                    return Err(ResourceError::WouldDeadlock {
+                        held: holding.clone(),
+                        requested: requested.clone(),
+                        cycle,
+                    });
+                }
+            }
+        }
+        for holding in held {
+            let edges = state.order.entry(holding.clone()).or_default();
+            edges.extend(names.iter().cloned());
+        }
+
+        while !state.available(names) {
+            if let Err(reason) = context.checkpoint() {
+                return Err(ResourceError::Cancelled(reason));
+            }
+            state = self
+                .shared
+                .released
+                .wait_timeout(state, WAIT_INTERVAL)
+                .unwrap()
+                .0;
+        }
+        for name in names {
+            *state.in_use.entry(name.clone()).or_insert(0) += 1;
+        }
+        Ok(Lease {
+            pool: self.clone(),
+            names: names.to_vec(),
+        })
+    }
+}
+
+impl Default for ResourcePool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Resources taken for one run, given back when it ends.
+struct Lease {
+    pool: ResourcePool,
+    names: Vec<String>,
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut state = self.pool.shared.state.lock().unwrap();
+        for name in &self.names {
+            if let Some(count) = state.in_use.get_mut(name) {
+                *count -= 1;
+            }
+        }
+        self.pool.shared.released.notify_all();
+    }
+}
+
+// A decorator that runs a job only while it holds its resources in `pool`. Jobs below
+// it see those resources as held and do not take them again.
+pub struct Guarded {
+    inner: Box<dyn JobImpl>,
+    pool: ResourcePool,
+    resources: Vec<String>,
+    status: Option<JobStatus>,
+}
+
+impl Guarded {
+    pub fn new(inner: Box<dyn JobImpl>, pool: &ResourcePool, resources: &[&str]) -> Self {
+        let mut resources: Vec<String> = resources.iter().map(|name| name.to_string()).collect();
+        resources.sort();
+        resources.dedup();
+        Guarded {
+            inner,
+            pool: pool.clone(),
+            resources,
+            status: None,
+        }
+    }
+
+    fn label(&self) -> String {
+        match self.inner.outline().id {
+            Some(id) => format!("job {}", id),
+            None => "job group".to_string(),
+        }
+    }
+}
+
+impl JobImpl for Guarded {
+    fn run(&mut self, context: &JobContext) -> JobReport {
+        let started_at = context.now();
+        self.status = None;
+        let needed: Vec<String> = self
+            .resources
+            .iter()
+            .filter(|name| !context.resources.contains(name))
+            .cloned()
+            .collect();
+
+        let _lease = match self.pool.acquire(&needed, &context.resources, context) {
+            Ok(lease) => lease,
+            Err(error) => {
+                let status = match error {
+                    ResourceError::Cancelled(CancelReason::Stopped) => JobStatus::Stopped,
+                    ResourceError::Cancelled(CancelReason::Paused) => JobStatus::Paused,
+                    _ => JobStatus::Failed,
+                };
+                self.status = Some(status);
+                context.record(status);
+                return JobReport::new(
+                    self.inner.outline().id,
+                    status,
+                    format!("Could not start {}: {}", self.label(), error),
+                )
+                .with_span(started_at, context.now());
+            }
+        };
+
+        let mut context = context.clone();
+        context.resources.extend(needed);
+        self.inner.run(&context)
+    }
+
+    fn stop(&mut self) -> JobReport {
+        self.status = None;
+        self.inner.stop()
+    }
+
+    fn pause(&mut self) -> JobReport {
+        self.inner.pause()
+    }
+
+    // A run that never got its resources did not reach the inner job.
+    fn status(&self) -> JobStatus {
+        self.status.unwrap_or_else(|| self.inner.status())
+    }
+
+    fn outline(&self) -> JobOutline {
+        let mut outline = self.inner.outline();
+        outline.status = self.status();
+        outline
+    }
+
+    fn compensate(&mut self, context: &JobContext) -> Option<JobReport> {
+        self.inner.compensate(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{Job, MultipleJob, SingleJob};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    // A job that records how many jobs sharing `running` overlap with it.
+    fn overlapping(
+        id: u32,
+        running: &Arc<AtomicUsize>,
+        peak: &Arc<AtomicUsize>,
+    ) -> Box<dyn JobImpl> {
+        let (running, peak) = (Arc::clone(running), Arc::clone(peak));
+        Box::new(SingleJob::with_task(id, move || {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok::<_, String>("ok".to_string())
+        }))
+    }
+
+    #[test]
+    fn test_exclusive_resource_serializes_parallel_jobs() {
+        let pool = ResourcePool::new();
+        let (running, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let jobs: Vec<Box<dyn JobImpl>> = (1..=3)
+            .map(|id| {
+                Box::new(Guarded::new(
+                    overlapping(id, &running, &peak),
+                    &pool,
+                    &["db-migrations"],
+                )) as Box<dyn JobImpl>
+            })
+            .collect();
+        let group = MultipleJob::new(jobs).with_max_concurrency(3);
+        let report = Job::new(Box::new(group)).run().unwrap();
+
+        assert_eq!(report.status, JobStatus::Completed);
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.in_use("db-migrations"), 0);
+    }
+
+    #[test]
+    fn test_concurrency_group_caps_parallel_jobs() {
+        let pool = ResourcePool::new().with_limit("uploads", 2);
+        let (running, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let jobs: Vec<Box<dyn JobImpl>> = (1..=4)
+            .map(|id| {
+                Box::new(Guarded::new(
+                    overlapping(id, &running, &peak),
+                    &pool,
+                    &["uploads"],
+                )) as Box<dyn JobImpl>
+            })
+            .collect();
+        let group = MultipleJob::new(jobs).with_max_concurrency(4);
+        Job::new(Box::new(group)).run().unwrap();
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_reversed_nested_order_is_rejected() {
+        let pool = ResourcePool::new();
+        let nested = |id: u32, outer: &str, inner: &str| -> Box<dyn JobImpl> {
+            let leaf = Guarded::new(Box::new(SingleJob::new(id)), &pool, &[inner, outer]);
+            let group = MultipleJob::new(vec![Box::new(leaf)]);
+            Box::new(Guarded::new(Box::new(group), &pool, &[outer]))
+        };
+
+        Job::new(nested(1, "accounts", "ledger")).run().unwrap();
+        let report = Job::new(nested(2, "ledger", "accounts")).run().unwrap();
+
+        assert_eq!(report.status, JobStatus::Failed);
+        assert_eq!(
+            report.children[0].output,
+            "Could not start job 2: taking 'accounts' while holding 'ledger' could deadlock: accounts -> ledger -> accounts"
+        );
+        assert_eq!(pool.in_use("ledger"), 0);
+    }
+}
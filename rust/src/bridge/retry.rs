@@ -0,0 +1,289 @@
+use super::report::JobReport;
+use super::{JobContext, JobImpl, JobOutline, JobStatus};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How long to wait before each retry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    Fixed(Duration),
+    Linear {
+        initial: Duration,
+        step: Duration,
+    },
+    Exponential {
+        initial: Duration,
+        factor: u32,
+        max: Duration,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Backoff,
+    jitter: f64,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, backoff: Backoff) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            backoff,
+            jitter: 0.0,
+        }
+    }
+
+    // Spreads each delay randomly by up to `fraction` of itself in either direction.
+    pub fn with_jitter(mut self, fraction: f64) -> Self {
+        self.jitter = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    // The delay before the `retry`-th retry (1-based), before jitter is applied.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let steps = retry.saturating_sub(1);
+        match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            // Delays too long to represent are capped rather than allowed to overflow.
+            Backoff::Linear { initial, step } => step
+                .checked_mul(steps)
+                .and_then(|increase| initial.checked_add(increase))
+                .unwrap_or(Duration::MAX),
+            Backoff::Exponential {
+                initial,
+                factor,
+                max,
+            } => {
+                let multiplier = factor.checked_pow(steps).unwrap_or(u32::MAX);
+                initial.checked_mul(multiplier).unwrap_or(max).min(max)
+            }
+        }
+    }
+
+    fn jittered(&self, delay: Duration, random: f64) -> Duration {
+        Duration::try_from_secs_f64(
+            delay.as_secs_f64() * (1.0 + self.jitter * (2.0 * random - 1.0)),
+        )
+        .unwrap_or(Duration::MAX)
+    }
+}
+
+// The outcome of one run of the wrapped job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attempt {
+    pub number: u32,
+    pub delay: Duration,
+    pub report: JobReport,
+}
+
+// A decorating "Implementor" that re-runs its inner job while it ends up `Failed`.
+pub struct RetryJob {
+    inner: Box<dyn JobImpl>,
+    policy: RetryPolicy,
+    attempts: Vec<Attempt>,
+    random_state: u64,
+}
+
+impl RetryJob {
+    pub fn new(inner: Box<dyn JobImpl>, policy: RetryPolicy) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+        RetryJob {
+            inner,
+            policy,
+            attempts: Vec::new(),
+            // xorshift must never be seeded with zero.
+            random_state: seed | 1,
+        }
+    }
+
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+
+    // A uniform value in [0, 1) from a xorshift generator; good enough for spreading retries.
+    fn next_random(&mut self) -> f64 {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        (self.random_state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl JobImpl for RetryJob {
+    fn run(&mut self, context: &JobContext) -> JobReport {
+        let started_at = context.now();
+        self.attempts.clear();
+
+        for number in 1..=self.policy.max_attempts {
+            let delay = if number == 1 {
+                Duration::ZERO
+            } else {
+                let random = self.next_random();
+                self.policy.jittered(self.policy.delay(number - 1), random)
+            };
+            if !context.cancellation().sleep(delay) {
+                break;
+            }
+
+            let report = self.inner.run(context);
+            let failed = report.status == JobStatus::Failed;
+            self.attempts.push(Attempt {
+                number,
+                delay,
+                report,
+            });
+            if !failed || context.is_cancelled() {
+                break;
+            }
+        }
+
+        let children: Vec<JobReport> = self
+            .attempts
+            .iter()
+            .map(|attempt| {
+                let mut report = attempt.report.clone();
+                report.output = format!(
+                    "Attempt {} of {} after {:?}: {}",
+                    attempt.number, self.policy.max_attempts, attempt.delay, report.output
+                );
+                report
+            })
+            .collect();
+        let id = children.last().and_then(|report| report.id);
+        JobReport::new(id, self.inner.status(), "Running job with retries:")
+            .with_span(started_at, context.now())
+            .with_children(children)
+            .with_retries(self.attempts.len().saturating_sub(1) as u32)
+    }
+
+    fn stop(&mut self) -> JobReport {
+        self.inner.stop()
+    }
+
+    fn pause(&mut self) -> JobReport {
+        self.inner.pause()
+    }
+
+    fn compensate(&mut self, context: &JobContext) -> Option<JobReport> {
+        self.inner.compensate(context)
+    }
+
+    fn status(&self) -> JobStatus {
+        self.inner.status()
+    }
+
+    fn outline(&self) -> JobOutline {
+        self.inner.outline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{Job, SingleJob};
+
+    #[test]
+    fn test_backoff_delays() {
+        let fixed = RetryPolicy::new(3, Backoff::Fixed(Duration::from_millis(5)));
+        assert_eq!(fixed.max_attempts(), 3);
+        assert_eq!(
+            RetryPolicy::new(0, Backoff::Fixed(Duration::ZERO)).max_attempts(),
+            1
+        );
+        assert_eq!(fixed.delay(1), Duration::from_millis(5));
+        assert_eq!(fixed.delay(4), Duration::from_millis(5));
+
+        let linear = RetryPolicy::new(
+            3,
+            Backoff::Linear {
+                initial: Duration::from_millis(10),
+                step: Duration::from_millis(5),
+            },
+        );
+        assert_eq!(linear.delay(1), Duration::from_millis(10));
+        assert_eq!(linear.delay(3), Duration::from_millis(20));
+        let huge = RetryPolicy::new(
+            3,
+            Backoff::Linear {
+                initial: Duration::MAX,
+                step: Duration::from_secs(1),
+            },
+        );
+        assert_eq!(huge.delay(2), Duration::MAX);
+        assert_eq!(huge.delay(u32::MAX), Duration::MAX);
+        assert_eq!(
+            huge.with_jitter(0.5).jittered(Duration::MAX, 0.99),
+            Duration::MAX
+        );
+
+        let exponential = RetryPolicy::new(
+            5,
+            Backoff::Exponential {
+                initial: Duration::from_millis(10),
+                factor: 2,
+                max: Duration::from_millis(50),
+            },
+        );
+        assert_eq!(exponential.delay(1), Duration::from_millis(10));
+        assert_eq!(exponential.delay(3), Duration::from_millis(40));
+        assert_eq!(exponential.delay(4), Duration::from_millis(50));
+        assert_eq!(exponential.delay(64), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_retry_job_records_attempt_history() {
+        let mut calls = 0;
+        let flaky = SingleJob::with_task(1, move || {
+            calls += 1;
+            if calls < 3 {
+                Err(format!("flaky {}", calls))
+            } else {
+                Ok("done".to_string())
+            }
+        });
+        let policy = RetryPolicy::new(5, Backoff::Fixed(Duration::from_millis(1)));
+        let mut job = Job::new(Box::new(RetryJob::new(Box::new(flaky), policy)));
+
+        let expected_run_output = "Running job with retries:\nAttempt 1 of 5 after 0ns: Single job 1 failed: flaky 1\nAttempt 2 of 5 after 1ms: Single job 1 failed: flaky 2\nAttempt 3 of 5 after 1ms: Single job 1 completed: done";
+        let report = job.run().unwrap();
+        assert_eq!(report.to_string(), expected_run_output);
+        assert_eq!(report.retries, 2);
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_retry_job_gives_up_after_max_attempts() {
+        let broken = SingleJob::with_task(2, || Err("broken"));
+        let policy = RetryPolicy::new(3, Backoff::Fixed(Duration::ZERO));
+        let mut retry_job = RetryJob::new(Box::new(broken), policy);
+
+        retry_job.run(&JobContext::default());
+        assert_eq!(retry_job.status(), JobStatus::Failed);
+        let statuses: Vec<JobStatus> = retry_job
+            .attempts()
+            .iter()
+            .map(|a| a.report.status)
+            .collect();
+        assert_eq!(statuses, vec![JobStatus::Failed; 3]);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let broken = SingleJob::with_task(3, || Err("broken"));
+        let policy = RetryPolicy::new(6, Backoff::Fixed(Duration::from_millis(2))).with_jitter(0.5);
+        let mut retry_job = RetryJob::new(Box::new(broken), policy);
+
+        retry_job.run(&JobContext::default());
+        for attempt in &retry_job.attempts()[1..] {
+            assert!(attempt.delay >= Duration::from_millis(1));
+            assert!(attempt.delay <= Duration::from_millis(3));
+        }
+    }
+}
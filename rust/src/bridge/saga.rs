@@ -0,0 +1,195 @@
+use super::report::JobReport;
+use super::{JobContext, JobImpl, JobOutline, JobStatus, Task};
+use std::fmt::Display;
+
+// A decorator that gives a job a compensating action, run to undo the job's work when
+// a group it completed in fails later on.
+pub struct Compensated {
+    inner: Box<dyn JobImpl>,
+    action: Task,
+    // Set once the action has undone a run, until the job runs again.
+    rolled_back: bool,
+}
+
+impl Compensated {
+    pub fn new<F, E>(inner: Box<dyn JobImpl>, mut action: F) -> Self
+    where
+        F: FnMut(&JobContext) -> Result<String, E> + Send + 'static,
+        E: Display,
+    {
+        Compensated {
+            inner,
+            action: Box::new(move |context| action(context).map_err(|e| e.to_string())),
+            rolled_back: false,
+        }
+    }
+
+    fn label(&self) -> String {
+        match self.inner.outline().id {
+            Some(id) => format!("job {}", id),
+            None => "job group".to_string(),
+        }
+    }
+}
+
+impl JobImpl for Compensated {
+    fn run(&mut self, context: &JobContext) -> JobReport {
+        self.rolled_back = false;
+        self.inner.run(context)
+    }
+
+    fn stop(&mut self) -> JobReport {
+        self.inner.stop()
+    }
+
+    fn pause(&mut self) -> JobReport {
+        self.inner.pause()
+    }
+
+    // A rolled back job is as good as one that never ran, so a rerun of its group does
+    // the work again.
+    fn status(&self) -> JobStatus {
+        if self.rolled_back {
+            JobStatus::Pending
+        } else {
+            self.inner.status()
+        }
+    }
+
+    fn outline(&self) -> JobOutline {
+        let mut outline = self.inner.outline();
+        outline.status = self.status();
+        outline
+    }
+
+    fn compensate(&mut self, context: &JobContext) -> Option<JobReport> {
+        let started_at = context.now();
+        let id = self.inner.outline().id;
+        let report = match (self.action)(context) {
+            Ok(output) => {
+                self.rolled_back = true;
+                JobReport::new(
+                    id,
+                    JobStatus::Completed,
+                    format!("Compensated {}: {}", self.label(), output),
+                )
+            }
+            Err(error) => JobReport::new(
+                id,
+                JobStatus::Failed,
+                format!("Compensation for {} failed: {}", self.label(), error),
+            ),
+        };
+        Some(report.with_span(started_at, context.now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{Job, MultipleJob, SingleJob};
+    use std::sync::{Arc, Mutex};
+
+    fn step(id: u32, succeeds: bool, log: &Arc<Mutex<Vec<String>>>) -> Box<dyn JobImpl> {
+        let (done, undone) = (Arc::clone(log), Arc::clone(log));
+        let job = SingleJob::with_task(id, move || {
+            done.lock().unwrap().push(format!("do {}", id));
+            if succeeds { Ok("ok") } else { Err("boom") }.map(str::to_string)
+        });
+        Box::new(Compensated::new(Box::new(job), move |_: &JobContext| {
+            undone.lock().unwrap().push(format!("undo {}", id));
+            Ok::<_, String>("undone".to_string())
+        }))
+    }
+
+    fn log_of(log: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+        log.lock().unwrap().drain(..).collect()
+    }
+
+    #[test]
+    fn test_failed_group_compensates_completed_jobs_in_reverse() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let group = MultipleJob::new(vec![
+            step(1, true, &log),
+            step(2, true, &log),
+            step(3, false, &log),
+        ]);
+        let mut job = Job::new(Box::new(group));
+        let report = job.run().unwrap();
+
+        assert_eq!(job.status(), JobStatus::Failed);
+        assert_eq!(
+            log_of(&log),
+            vec!["do 1", "do 2", "do 3", "undo 2", "undo 1"]
+        );
+        let outputs: Vec<&str> = report
+            .compensations
+            .iter()
+            .map(|compensation| compensation.output.as_str())
+            .collect();
+        assert_eq!(
+            outputs,
+            vec!["Compensated job 2: undone", "Compensated job 1: undone"]
+        );
+        assert!(report.compensation_failures().is_empty());
+
+        // Rolled back jobs run again on a rerun, instead of being kept as completed.
+        job.run().unwrap();
+        assert_eq!(
+            log_of(&log),
+            vec!["do 1", "do 2", "do 3", "undo 2", "undo 1"]
+        );
+    }
+
+    #[test]
+    fn test_failed_compensations_are_reported_separately() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mounted = SingleJob::with_task(2, || Ok::<_, String>("mounted".to_string()));
+        let stuck = Compensated::new(Box::new(mounted), |_: &JobContext| {
+            Err::<String, _>("volume is busy")
+        });
+        let group = MultipleJob::new(vec![
+            step(1, true, &log),
+            Box::new(stuck),
+            step(3, false, &log),
+        ]);
+        let report = Job::new(Box::new(group)).run().unwrap();
+
+        let failures = report.compensation_failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].output,
+            "Compensation for job 2 failed: volume is busy"
+        );
+        assert_eq!(
+            report.notes.last().unwrap(),
+            "Compensation failed for jobs 2; their changes were not undone"
+        );
+        // One failed undo does not stop the others.
+        assert_eq!(log_of(&log), vec!["do 1", "do 3", "undo 1"]);
+    }
+
+    #[test]
+    fn test_completed_nested_group_is_compensated_as_a_whole() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let inner = MultipleJob::new(vec![step(1, true, &log), step(2, true, &log)]);
+        let outer = MultipleJob::new(vec![Box::new(inner), step(3, false, &log)]);
+        let report = Job::new(Box::new(outer)).run().unwrap();
+
+        assert_eq!(
+            log_of(&log),
+            vec!["do 1", "do 2", "do 3", "undo 2", "undo 1"]
+        );
+        assert_eq!(report.compensations.len(), 1);
+        assert_eq!(
+            report.compensations[0].output,
+            "Compensating multiple jobs:"
+        );
+        assert_eq!(report.compensations[0].status, JobStatus::Completed);
+        assert!(
+            report
+                .render(crate::bridge::report::ReportFormat::Tree)
+                .contains("compensation ")
+        );
+    }
+}
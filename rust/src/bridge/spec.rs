@@ -0,0 +1,785 @@
+use super::command::CommandJob;
+use super::retry::{Backoff, RetryJob, RetryPolicy};
+use super::timeout::TimeoutJob;
+use super::{FailurePolicy, Job, JobContext, JobImpl, MultipleJob, SingleJob};
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// A job tree described in a text file. The format is JSON with `//` line comments:
+//
+//   {
+//     "type": "group", "concurrency": 2, "failure_policy": "fail-fast",
+//     "jobs": [
+//       { "type": "command", "id": 1, "program": "make", "args": ["build"],
+//         "timeout_ms": 60000, "retry": { "attempts": 3, "delay_ms": 500 } },
+//       { "type": "single", "id": 2, "sleep_ms": 100 }
+//     ]
+//   }
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobSpec {
+    pub kind: JobKind,
+    pub timeout: Option<Duration>,
+    pub retry: Option<RetryPolicy>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobKind {
+    // A placeholder job, which optionally takes some time to finish.
+    Single {
+        id: u32,
+        sleep: Option<Duration>,
+    },
+    Command {
+        id: u32,
+        program: String,
+        args: Vec<String>,
+    },
+    Group {
+        jobs: Vec<JobSpec>,
+        max_concurrency: usize,
+        failure_policy: FailurePolicy,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecError {
+    Io(String),
+    // Lines and columns count from 1.
+    Invalid {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecError::Io(error) => write!(f, "cannot read job spec: {}", error),
+            SpecError::Invalid {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+        }
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+// One line per job, indented by depth, in the style of `ReportFormat::Tree`.
+impl Display for JobSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rendered = String::new();
+        self.render(0, &mut rendered);
+        f.write_str(&rendered)
+    }
+}
+
+impl JobSpec {
+    pub fn parse(text: &str) -> Result<Self, SpecError> {
+        let value = Parser::new(text).parse_document()?;
+        let mut ids = HashSet::new();
+        JobSpec::from_value(&value, &mut ids)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SpecError> {
+        let text = fs::read_to_string(path).map_err(|error| SpecError::Io(error.to_string()))?;
+        JobSpec::parse(&text)
+    }
+
+    // Retries wrap the timeout, so every attempt gets the full deadline.
+    pub fn build(&self) -> Box<dyn JobImpl> {
+        let mut job: Box<dyn JobImpl> = match &self.kind {
+            // A single job without a sleep has nothing to do, but still completes.
+            JobKind::Single { id, sleep } => {
+                let sleep = *sleep;
+                Box::new(SingleJob::with_context_task(
+                    *id,
+                    move |context: &JobContext| match sleep {
+                        Some(sleep) => {
+                            context.cancellation().sleep(sleep);
+                            context.checkpoint().map_err(|reason| reason.to_string())?;
+                            Ok::<_, String>(format!("slept {:?}", sleep))
+                        }
+                        None => Ok("done".to_string()),
+                    },
+                ))
+            }
+            JobKind::Command { id, program, args } => {
+                Box::new(CommandJob::new(*id, program.clone()).args(args.clone()))
+            }
+            JobKind::Group {
+                jobs,
+                max_concurrency,
+                failure_policy,
+            } => Box::new(
+                MultipleJob::new(jobs.iter().map(JobSpec::build).collect())
+                    .with_max_concurrency(*max_concurrency)
+                    .with_failure_policy(*failure_policy),
+            ),
+        };
+        if let Some(timeout) = self.timeout {
+            job = Box::new(TimeoutJob::new(job, timeout));
+        }
+        if let Some(policy) = self.retry {
+            job = Box::new(RetryJob::new(job, policy));
+        }
+        job
+    }
+
+    pub fn build_job(&self) -> Job {
+        Job::new(self.build())
+    }
+
+    // Overrides the concurrency of every group in the tree.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        if let JobKind::Group {
+            jobs,
+            max_concurrency: own,
+            ..
+        } = &mut self.kind
+        {
+            *own = max_concurrency.max(1);
+            *jobs = jobs
+                .drain(..)
+                .map(|job| job.with_max_concurrency(max_concurrency))
+                .collect();
+        }
+        self
+    }
+
+    fn render(&self, depth: usize, rendered: &mut String) {
+        let mut line = format!("{}- ", "  ".repeat(depth));
+        match &self.kind {
+            JobKind::Single { id, sleep } => {
+                line.push_str(&format!("single {}", id));
+                if let Some(sleep) = sleep {
+                    line.push_str(&format!(" (sleeps {:?})", sleep));
+                }
+            }
+            JobKind::Command { id, program, args } => {
+                line.push_str(&format!("command {}: {}", id, program));
+                for arg in args {
+                    line.push(' ');
+                    line.push_str(arg);
+                }
+            }
+            JobKind::Group {
+                max_concurrency,
+                failure_policy,
+                ..
+            } => line.push_str(&format!(
+                "group (concurrency {}, {})",
+                max_concurrency, failure_policy
+            )),
+        }
+        if let Some(timeout) = self.timeout {
+            line.push_str(&format!(" [timeout {:?}]", timeout));
+        }
+        if let Some(retry) = self.retry {
+            line.push_str(&format!(" [up to {} attempts]", retry.max_attempts()));
+        }
+
+        if !rendered.is_empty() {
+            rendered.push('\n');
+        }
+        rendered.push_str(&line);
+        if let JobKind::Group { jobs, .. } = &self.kind {
+            for job in jobs {
+                job.render(depth + 1, rendered);
+            }
+        }
+    }
+
+    fn from_value(value: &Value, ids: &mut HashSet<u32>) -> Result<Self, SpecError> {
+        let object = Object::new(value)?;
+        let kind = match object.string("type")?.as_str() {
+            "single" => {
+                object.allow(&["type", "id", "sleep_ms", "timeout_ms", "retry"])?;
+                JobKind::Single {
+                    id: object.id(ids)?,
+                    sleep: object.optional_millis("sleep_ms")?,
+                }
+            }
+            "command" => {
+                object.allow(&["type", "id", "program", "args", "timeout_ms", "retry"])?;
+                let args = match object.optional("args") {
+                    Some(args) => args
+                        .array()?
+                        .iter()
+                        .map(Value::string)
+                        .collect::<Result<_, _>>()?,
+                    None => Vec::new(),
+                };
+                JobKind::Command {
+                    id: object.id(ids)?,
+                    program: object.string("program")?,
+                    args,
+                }
+            }
+            "group" => {
+                object.allow(&[
+                    "type",
+                    "jobs",
+                    "concurrency",
+                    "failure_policy",
+                    "timeout_ms",
+                    "retry",
+                ])?;
+                let jobs = object
+                    .required("jobs")?
+                    .array()?
+                    .iter()
+                    .map(|job| JobSpec::from_value(job, ids))
+                    .collect::<Result<_, _>>()?;
+                let failure_policy = match object.optional("failure_policy") {
+                    Some(policy) => parse_failure_policy(policy)?,
+                    None => FailurePolicy::ContinueOnError,
+                };
+                JobKind::Group {
+                    jobs,
+                    max_concurrency: match object.optional("concurrency") {
+                        Some(concurrency) => concurrency.integer()? as usize,
+                        None => 1,
+                    },
+                    failure_policy,
+                }
+            }
+            other => {
+                return Err(object.required("type")?.error(format!(
+                    "unknown job type \"{}\"; expected single, command or group",
+                    other
+                )));
+            }
+        };
+
+        let retry = match object.optional("retry") {
+            Some(retry) => Some(parse_retry(retry)?),
+            None => None,
+        };
+        Ok(JobSpec {
+            kind,
+            timeout: object.optional_millis("timeout_ms")?,
+            retry,
+        })
+    }
+}
+
+fn parse_failure_policy(value: &Value) -> Result<FailurePolicy, SpecError> {
+    let text = value.string()?;
+    let tolerated = text
+        .strip_prefix("tolerate ")
+        .and_then(|rest| rest.strip_suffix(" failures"))
+        .and_then(|count| count.parse().ok());
+    match (text.as_str(), tolerated) {
+        ("fail-fast", _) => Ok(FailurePolicy::FailFast),
+        ("continue-on-error", _) => Ok(FailurePolicy::ContinueOnError),
+        (_, Some(count)) => Ok(FailurePolicy::TolerateFailures(count)),
+        _ => Err(value.error(format!(
+            "unknown failure policy \"{}\"; expected fail-fast, continue-on-error or tolerate N failures",
+            text
+        ))),
+    }
+}
+
+fn parse_retry(value: &Value) -> Result<RetryPolicy, SpecError> {
+    let object = Object::new(value)?;
+    object.allow(&[
+        "attempts", "backoff", "delay_ms", "step_ms", "factor", "max_ms", "jitter",
+    ])?;
+    let delay = object.optional_millis("delay_ms")?.unwrap_or_default();
+    let backoff = match object.optional("backoff") {
+        None => Backoff::Fixed(delay),
+        Some(backoff) => match backoff.string()?.as_str() {
+            "fixed" => Backoff::Fixed(delay),
+            "linear" => Backoff::Linear {
+                initial: delay,
+                step: object.optional_millis("step_ms")?.unwrap_or(delay),
+            },
+            "exponential" => Backoff::Exponential {
+                initial: delay,
+                factor: match object.optional("factor") {
+                    Some(factor) => factor.integer()?,
+                    None => 2,
+                },
+                max: object.optional_millis("max_ms")?.unwrap_or(Duration::MAX),
+            },
+            other => {
+                return Err(backoff.error(format!(
+                    "unknown backoff \"{}\"; expected fixed, linear or exponential",
+                    other
+                )));
+            }
+        },
+    };
+
+    let mut policy = RetryPolicy::new(object.required("attempts")?.integer()?, backoff);
+    if let Some(jitter) = object.optional("jitter") {
+        policy = policy.with_jitter(jitter.number()?);
+    }
+    Ok(policy)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Value {
+    line: usize,
+    column: usize,
+    kind: ValueKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ValueKind {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn error(&self, message: impl Into<String>) -> SpecError {
+        SpecError::Invalid {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self.kind {
+            ValueKind::Null => "null",
+            ValueKind::Bool(_) => "a boolean",
+            ValueKind::Number(_) => "a number",
+            ValueKind::String(_) => "a string",
+            ValueKind::Array(_) => "an array",
+            ValueKind::Object(_) => "an object",
+        }
+    }
+
+    fn string(&self) -> Result<String, SpecError> {
+        match &self.kind {
+            ValueKind::String(text) => Ok(text.clone()),
+            _ => Err(self.error(format!("expected a string, found {}", self.describe()))),
+        }
+    }
+
+    fn number(&self) -> Result<f64, SpecError> {
+        match self.kind {
+            ValueKind::Number(number) => Ok(number),
+            _ => Err(self.error(format!("expected a number, found {}", self.describe()))),
+        }
+    }
+
+    fn integer(&self) -> Result<u32, SpecError> {
+        let number = self.number()?;
+        if number.fract() != 0.0 || !(0.0..=u32::MAX as f64).contains(&number) {
+            return Err(self.error(format!("expected a whole number, found {}", number)));
+        }
+        Ok(number as u32)
+    }
+
+    fn array(&self) -> Result<&[Value], SpecError> {
+        match &self.kind {
+            ValueKind::Array(items) => Ok(items),
+            _ => Err(self.error(format!("expected an array, found {}", self.describe()))),
+        }
+    }
+}
+
+// Field lookups on an object value, with errors pointing at the offending field.
+struct Object<'a> {
+    value: &'a Value,
+    fields: &'a [(String, Value)],
+}
+
+impl<'a> Object<'a> {
+    fn new(value: &'a Value) -> Result<Self, SpecError> {
+        match &value.kind {
+            ValueKind::Object(fields) => Ok(Object { value, fields }),
+            _ => Err(value.error(format!("expected an object, found {}", value.describe()))),
+        }
+    }
+
+    fn allow(&self, names: &[&str]) -> Result<(), SpecError> {
+        match self
+            .fields
+            .iter()
+            .find(|(name, _)| !names.contains(&name.as_str()))
+        {
+            Some((name, value)) => Err(value.error(format!("unknown field \"{}\"", name))),
+            None => Ok(()),
+        }
+    }
+
+    fn optional(&self, name: &str) -> Option<&'a Value> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+    }
+
+    fn required(&self, name: &str) -> Result<&'a Value, SpecError> {
+        self.optional(name)
+            .ok_or_else(|| self.value.error(format!("missing field \"{}\"", name)))
+    }
+
+    fn string(&self, name: &str) -> Result<String, SpecError> {
+        self.required(name)?.string()
+    }
+
+    fn optional_millis(&self, name: &str) -> Result<Option<Duration>, SpecError> {
+        match self.optional(name) {
+            Some(value) => Ok(Some(Duration::from_millis(value.integer()? as u64))),
+            None => Ok(None),
+        }
+    }
+
+    // Job ids must be unique across the whole file.
+    fn id(&self, ids: &mut HashSet<u32>) -> Result<u32, SpecError> {
+        let value = self.required("id")?;
+        let id = value.integer()?;
+        if !ids.insert(id) {
+            return Err(value.error(format!("job id {} is used more than once", id)));
+        }
+        Ok(id)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Self {
+        Parser {
+            chars: text.chars().collect(),
+            position: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> SpecError {
+        SpecError::Invalid {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SpecError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}', found end of file", expected))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('/') if self.chars.get(self.position + 1) == Some(&'/') => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Value, SpecError> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(value),
+            Some(c) => Err(self.error(format!("unexpected '{}' after the job spec", c))),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, SpecError> {
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+        let kind = match self.peek() {
+            Some('{') => self.parse_object()?,
+            Some('[') => self.parse_array()?,
+            Some('"') => ValueKind::String(self.parse_string()?),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number()?,
+            Some(c) if c.is_ascii_alphabetic() => self.parse_literal()?,
+            Some(c) => return Err(self.error(format!("unexpected '{}'", c))),
+            None => return Err(self.error("unexpected end of file")),
+        };
+        Ok(Value { line, column, kind })
+    }
+
+    fn parse_object(&mut self) -> Result<ValueKind, SpecError> {
+        self.expect('{')?;
+        let mut fields: Vec<(String, Value)> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(ValueKind::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let (line, column) = (self.line, self.column);
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a field name in double quotes"));
+            }
+            let name = self.parse_string()?;
+            if fields.iter().any(|(existing, _)| *existing == name) {
+                return Err(SpecError::Invalid {
+                    line,
+                    column,
+                    message: format!("field \"{}\" is given more than once", name),
+                });
+            }
+            self.expect(':')?;
+            fields.push((name, self.parse_value()?));
+
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => {}
+                Some('}') => return Ok(ValueKind::Object(fields)),
+                _ => return Err(self.error("expected ',' or '}' after a field")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<ValueKind, SpecError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(ValueKind::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(ValueKind::Array(items)),
+                _ => return Err(self.error("expected ',' or ']' after an array item")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, SpecError> {
+        self.bump();
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let digits: String = (0..4).filter_map(|_| self.bump()).collect();
+                            u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    text.push(escaped);
+                }
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<ValueKind, SpecError> {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+        text.parse()
+            .map(ValueKind::Number)
+            .map_err(|_| self.error(format!("invalid number '{}'", text)))
+    }
+
+    fn parse_literal(&mut self) -> Result<ValueKind, SpecError> {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            word.push(c);
+            self.bump();
+        }
+        match word.as_str() {
+            "true" => Ok(ValueKind::Bool(true)),
+            "false" => Ok(ValueKind::Bool(false)),
+            "null" => Ok(ValueKind::Null),
+            _ => Err(self.error(format!("unexpected word '{}'", word))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::JobStatus;
+
+    const BATCH: &str = r#"
+        // Nightly batch
+        {
+          "type": "group",
+          "concurrency": 2,
+          "failure_policy": "tolerate 1 failures",
+          "jobs": [
+            { "type": "command", "id": 1, "program": "echo", "args": ["hello"] },
+            { "type": "command", "id": 2, "program": "sh", "args": ["-c", "exit 4"],
+              "retry": { "attempts": 2, "backoff": "linear", "delay_ms": 1 } },
+            { "type": "group", "jobs": [ { "type": "single", "id": 3, "sleep_ms": 1 } ],
+              "timeout_ms": 5000 }
+          ]
+        }
+    "#;
+
+    #[test]
+    fn test_parse_builds_the_described_tree() {
+        let spec = JobSpec::parse(BATCH).unwrap();
+        let JobKind::Group {
+            jobs,
+            max_concurrency,
+            failure_policy,
+        } = &spec.kind
+        else {
+            panic!("expected a group, got {:?}", spec.kind);
+        };
+        assert_eq!(*max_concurrency, 2);
+        assert_eq!(*failure_policy, FailurePolicy::TolerateFailures(1));
+        assert_eq!(
+            jobs[1].retry,
+            Some(RetryPolicy::new(
+                2,
+                Backoff::Linear {
+                    initial: Duration::from_millis(1),
+                    step: Duration::from_millis(1),
+                }
+            ))
+        );
+        assert_eq!(jobs[2].timeout, Some(Duration::from_secs(5)));
+
+        let mut job = spec.build_job();
+        assert_eq!(job.outline().ids(), vec![1, 2, 3]);
+        let report = job.run().unwrap();
+        assert_eq!(report.children[0].output, "Command job 1 completed: hello");
+        assert_eq!(report.children[1].children.len(), 2);
+        assert_eq!(job.outline().ids_with_status(JobStatus::Failed), vec![2]);
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_single_jobs_without_sleep_complete() {
+        let spec = JobSpec::parse(
+            r#"{"type":"group","jobs":[{"type":"single","id":1},{"type":"single","id":2}]}"#,
+        )
+        .unwrap();
+        let mut job = spec.build_job();
+        let report = job.run().unwrap();
+
+        assert_eq!(report.status, JobStatus::Completed);
+        assert_eq!(report.children[0].output, "Single job 1 completed: done");
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_display_and_parallel_override() {
+        let spec = JobSpec::parse(BATCH).unwrap().with_max_concurrency(4);
+        let expected = [
+            "- group (concurrency 4, tolerate 1 failures)",
+            "  - command 1: echo hello",
+            "  - command 2: sh -c exit 4 [up to 2 attempts]",
+            "  - group (concurrency 4, continue-on-error) [timeout 5s]",
+            "    - single 3 (sleeps 1ms)",
+        ];
+        assert_eq!(spec.to_string(), expected.join("\n"));
+    }
+
+    #[test]
+    fn test_syntax_errors_carry_line_and_column() {
+        let error = JobSpec::parse("{\n  \"type\": \"single\",\n  \"id\" 1\n}").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 3, column 8: expected ':', found '1'"
+        );
+
+        let error = JobSpec::parse("{ \"type\": \"single\", \"id\": 1 } }").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 1, column 31: unexpected '}' after the job spec"
+        );
+    }
+
+    #[test]
+    fn test_invalid_specs_point_at_the_offending_value() {
+        let error = |text: &str| JobSpec::parse(text).unwrap_err().to_string();
+
+        assert_eq!(
+            error("{ \"type\": \"batch\" }"),
+            "line 1, column 11: unknown job type \"batch\"; expected single, command or group"
+        );
+        assert_eq!(
+            error("{ \"type\": \"command\", \"id\": 1 }"),
+            "line 1, column 1: missing field \"program\""
+        );
+        assert_eq!(
+            error(
+                "{\"type\": \"group\", \"jobs\": [\n {\"type\": \"single\", \"id\": 7},\n {\"type\": \"single\", \"id\": 7}]}"
+            ),
+            "line 3, column 27: job id 7 is used more than once"
+        );
+        assert_eq!(
+            error("{ \"type\": \"single\", \"id\": 1, \"sleep\": 5 }"),
+            "line 1, column 39: unknown field \"sleep\""
+        );
+        assert_eq!(
+            JobSpec::load("/definitely/not/here.json"),
+            Err(SpecError::Io(
+                "No such file or directory (os error 2)".to_string()
+            ))
+        );
+    }
+}
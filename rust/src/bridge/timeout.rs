@@ -0,0 +1,162 @@
+use super::cancel::CancelReason;
+use super::report::JobReport;
+use super::{JobContext, JobImpl, JobOutline, JobStatus};
+use std::time::Duration;
+
+// A decorating "Implementor" that gives its inner job a deadline. Cooperative work sees
+// the deadline through its context; work that ignores it is still failed once it overruns.
+pub struct TimeoutJob {
+    inner: Box<dyn JobImpl>,
+    timeout: Duration,
+    timed_out: bool,
+}
+
+impl TimeoutJob {
+    pub fn new(inner: Box<dyn JobImpl>, timeout: Duration) -> Self {
+        TimeoutJob {
+            inner,
+            timeout,
+            timed_out: false,
+        }
+    }
+}
+
+impl JobImpl for TimeoutJob {
+    fn run(&mut self, context: &JobContext) -> JobReport {
+        let context = context.with_timeout(self.timeout);
+        let mut report = self.inner.run(&context);
+
+        self.timed_out = matches!(
+            context.cancellation().reason(),
+            Some(CancelReason::TimedOut(_))
+        );
+        if self.timed_out && report.status != JobStatus::Failed {
+            report.output = format!("Job timed out after {:?}: {}", self.timeout, report.output);
+            report.status = JobStatus::Failed;
+            context.record(JobStatus::Failed);
+        }
+        report
+    }
+
+    fn stop(&mut self) -> JobReport {
+        self.inner.stop()
+    }
+
+    fn pause(&mut self) -> JobReport {
+        self.inner.pause()
+    }
+
+    fn compensate(&mut self, context: &JobContext) -> Option<JobReport> {
+        self.inner.compensate(context)
+    }
+
+    fn status(&self) -> JobStatus {
+        if self.timed_out {
+            JobStatus::Failed
+        } else {
+            self.inner.status()
+        }
+    }
+
+    fn outline(&self) -> JobOutline {
+        JobOutline {
+            status: self.status(),
+            ..self.inner.outline()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::command::CommandJob;
+    use crate::bridge::{Job, SingleJob};
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn test_cooperative_job_fails_with_timeout_reason() {
+        let slow = SingleJob::with_context_task(1, |context: &JobContext| {
+            context.cancellation().sleep(Duration::from_secs(30));
+            Ok::<_, String>("finished".to_string())
+        });
+        let mut job = Job::new(Box::new(TimeoutJob::new(
+            Box::new(slow),
+            Duration::from_millis(20),
+        )));
+
+        let started = Instant::now();
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Single job 1 timed out after 20ms"
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(job.status(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_uncooperative_job_is_failed_after_overrunning() {
+        let slow = SingleJob::with_task(2, || {
+            thread::sleep(Duration::from_millis(30));
+            Ok::<_, String>("finished".to_string())
+        });
+        let mut job = Job::new(Box::new(TimeoutJob::new(
+            Box::new(slow),
+            Duration::from_millis(5),
+        )));
+
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Single job 2 timed out after 5ms"
+        );
+        assert_eq!(job.status(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_command_job_is_killed_at_deadline() {
+        let sleeper = CommandJob::new(3, "sleep").arg("30");
+        let mut job = Job::new(Box::new(TimeoutJob::new(
+            Box::new(sleeper),
+            Duration::from_millis(50),
+        )));
+
+        let started = Instant::now();
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Command job 3 timed out after 50ms"
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(job.status(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_shell_step_is_killed_at_deadline_with_what_it_started() {
+        let shell = CommandJob::new(5, "sh").args(["-c", "sleep 5; echo hi"]);
+        let mut job = Job::new(Box::new(TimeoutJob::new(
+            Box::new(shell),
+            Duration::from_millis(200),
+        )));
+
+        let started = Instant::now();
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Command job 5 timed out after 200ms"
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_job_within_deadline_is_untouched() {
+        let quick = SingleJob::with_task(4, || Ok::<_, String>("quick".to_string()));
+        let mut job = Job::new(Box::new(TimeoutJob::new(
+            Box::new(quick),
+            Duration::from_secs(5),
+        )));
+
+        assert_eq!(
+            job.run().unwrap().to_string(),
+            "Single job 4 completed: quick"
+        );
+        assert_eq!(job.status(), JobStatus::Completed);
+    }
+}
@@ -0,0 +1,381 @@
+use crate::bridge::JobStatus;
+use crate::bridge::control::{self, ControlServer};
+use crate::bridge::manager::JobManager;
+use crate::bridge::progress::JobEvent;
+use crate::bridge::report::ReportFormat;
+use crate::bridge::spec::JobSpec;
+use std::io::Write;
+use std::sync::Arc;
+use std::thread;
+
+const USAGE: &str = "usage: jobs <spec-file> [--dry-run] [--parallel N] [--report plain|tree|json] [--control SOCKET]";
+const CTL_USAGE: &str = "usage: ctl <socket> list | status ID | report ID [plain|tree|json] | stop ID | pause ID | resume ID";
+
+// Exit codes of the `jobs` subcommand.
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    spec_path: String,
+    dry_run: bool,
+    parallel: Option<usize>,
+    report: ReportFormat,
+    control: Option<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut spec_path = None;
+    let mut dry_run = false;
+    let mut parallel = None;
+    let mut report = ReportFormat::Plain;
+    let mut control = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--parallel" => {
+                let value = args.next().ok_or("--parallel needs a number")?;
+                let workers: usize = value
+                    .parse()
+                    .map_err(|_| format!("--parallel needs a number, not '{}'", value))?;
+                if workers == 0 {
+                    return Err("--parallel must be at least 1".to_string());
+                }
+                parallel = Some(workers);
+            }
+            "--report" => {
+                report = match args.next().map(String::as_str) {
+                    Some("plain") => ReportFormat::Plain,
+                    Some("tree") => ReportFormat::Tree,
+                    Some("json") => ReportFormat::Json,
+                    _ => return Err("--report needs one of plain, tree or json".to_string()),
+                };
+            }
+            "--control" => {
+                let socket = args.next().ok_or("--control needs a socket path")?;
+                control = Some(socket.to_string());
+            }
+            option if option.starts_with("--") => {
+                return Err(format!("unknown option '{}'", option));
+            }
+            path if spec_path.is_none() => spec_path = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument '{}'", extra)),
+        }
+    }
+
+    Ok(Options {
+        spec_path: spec_path.ok_or("missing the job spec file")?,
+        dry_run,
+        parallel,
+        report,
+        control,
+    })
+}
+
+fn describe(event: &JobEvent) -> Option<String> {
+    let JobEvent::StatusChanged { path, to, .. } = event else {
+        return None;
+    };
+    let path: Vec<String> = path.iter().map(|index| index.to_string()).collect();
+    Some(format!("/{} {:?}", path.join("/"), to))
+}
+
+fn is_final(status: JobStatus) -> bool {
+    matches!(
+        status,
+        JobStatus::Completed | JobStatus::Failed | JobStatus::Stopped
+    )
+}
+
+// The `jobs` subcommand: loads a job spec and runs it. Status changes go to `err` while
+// the job runs, and the final report goes to `out`. With `--control`, the job can be
+// queried and controlled through `ctl` while it runs. Returns the process exit code.
+pub fn jobs(args: &[String], out: &mut impl Write, err: &mut impl Write) -> i32 {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(message) => {
+            let _ = writeln!(err, "{}\n{}", message, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    let mut spec = match JobSpec::load(&options.spec_path) {
+        Ok(spec) => spec,
+        Err(error) => {
+            let _ = writeln!(err, "{}: {}", options.spec_path, error);
+            return EXIT_USAGE;
+        }
+    };
+    if let Some(parallel) = options.parallel {
+        spec = spec.with_max_concurrency(parallel);
+    }
+    if options.dry_run {
+        let _ = writeln!(out, "{}", spec);
+        return EXIT_OK;
+    }
+
+    let job = spec.build_job();
+    let events = job.subscribers().channel();
+    let statuses = job.subscribers().channel();
+    let manager = Arc::new(JobManager::new());
+    let id = match manager.register(job) {
+        Ok(id) => id,
+        Err(error) => {
+            let _ = writeln!(err, "{}", error);
+            return EXIT_USAGE;
+        }
+    };
+    let _server = match &options.control {
+        Some(socket) => match ControlServer::start(socket, Arc::clone(&manager)) {
+            Ok(server) => Some(server),
+            Err(error) => {
+                let _ = writeln!(err, "{}: {}", socket, error);
+                return EXIT_USAGE;
+            }
+        },
+        None => None,
+    };
+
+    // The job runs on its own thread so its events can be printed as they happen. The
+    // thread removes the job from the manager when done, which drops it, closes the
+    // channel and ends the loop below.
+    let runner = thread::spawn(move || {
+        let result = manager.run(id);
+        // A batch paused through the control socket waits there to be resumed or
+        // stopped, which the root announces by moving to a final status.
+        if !manager.status(id).is_some_and(is_final) {
+            for event in &statuses {
+                if let JobEvent::StatusChanged { path, to, .. } = event
+                    && path.is_empty()
+                    && is_final(to)
+                {
+                    break;
+                }
+            }
+        }
+        // A resume holds the job's lock until it has stored its report.
+        if let Some(job) = manager.get(id) {
+            drop(job.lock());
+        }
+        let result = result.map(|report| manager.report(id).unwrap_or(report));
+        let status = manager.status(id);
+        manager.remove(id);
+        (result, status)
+    });
+    for event in events {
+        if let Some(line) = describe(&event) {
+            let _ = writeln!(err, "{}", line);
+        }
+    }
+
+    match runner.join().expect("job runner thread panicked") {
+        (Ok(report), status) => {
+            let _ = writeln!(out, "{}", report.render(options.report));
+            if status == Some(JobStatus::Failed) {
+                EXIT_FAILED
+            } else {
+                EXIT_OK
+            }
+        }
+        (Err(error), _) => {
+            let _ = writeln!(err, "{}", error);
+            EXIT_FAILED
+        }
+    }
+}
+
+// The `ctl` subcommand: sends one request to the control socket of a running `jobs`
+// process and prints the answer. Returns the process exit code.
+pub fn ctl(args: &[String], out: &mut impl Write, err: &mut impl Write) -> i32 {
+    let [socket, request @ ..] = args else {
+        let _ = writeln!(err, "{}", CTL_USAGE);
+        return EXIT_USAGE;
+    };
+    if request.is_empty() {
+        let _ = writeln!(err, "{}", CTL_USAGE);
+        return EXIT_USAGE;
+    }
+
+    match control::send_request(socket, &request.join(" ")) {
+        Ok(Ok(lines)) => {
+            for line in lines {
+                let _ = writeln!(out, "{}", line);
+            }
+            EXIT_OK
+        }
+        Ok(Err(message)) => {
+            let _ = writeln!(err, "{}", message);
+            EXIT_FAILED
+        }
+        Err(error) => {
+            let _ = writeln!(err, "{}: {}", socket, error);
+            EXIT_FAILED
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn spec_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("cli-{}-{}.json", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn run(args: &[&str]) -> (i32, String, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = jobs(&args, &mut out, &mut err);
+        (
+            code,
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    const BATCH: &str = r#"{ "type": "group", "jobs": [
+        { "type": "command", "id": 1, "program": "echo", "args": ["hi"] },
+        { "type": "command", "id": 2, "program": "sh", "args": ["-c", "exit 1"] } ] }"#;
+
+    #[test]
+    fn test_parse_options() {
+        let args = |list: &[&str]| -> Vec<String> { list.iter().map(|a| a.to_string()).collect() };
+        assert_eq!(
+            parse_options(&args(&[
+                "batch.json",
+                "--parallel",
+                "3",
+                "--report",
+                "json",
+                "--control",
+                "batch.sock"
+            ])),
+            Ok(Options {
+                spec_path: "batch.json".to_string(),
+                dry_run: false,
+                parallel: Some(3),
+                report: ReportFormat::Json,
+                control: Some("batch.sock".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_options(&args(&["--parallel", "0", "batch.json"])),
+            Err("--parallel must be at least 1".to_string())
+        );
+        assert_eq!(
+            parse_options(&args(&["--dry-run"])),
+            Err("missing the job spec file".to_string())
+        );
+    }
+
+    #[test]
+    fn test_dry_run_prints_the_tree_without_running() {
+        let path = spec_file("dry-run", BATCH);
+        let (code, out, err) = run(&[path.to_str().unwrap(), "--dry-run", "--parallel", "2"]);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(code, 0);
+        assert_eq!(
+            out,
+            "- group (concurrency 2, continue-on-error)\n  - command 1: echo hi\n  - command 2: sh -c exit 1\n"
+        );
+        assert_eq!(err, "");
+    }
+
+    #[test]
+    fn test_failed_root_exits_non_zero_and_streams_statuses() {
+        let path = spec_file("failing", BATCH);
+        let (code, out, err) = run(&[path.to_str().unwrap(), "--report", "json"]);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(code, 1);
+        assert!(out.starts_with("{\"id\":null,\"status\":\"Failed\""));
+        assert_eq!(
+            err.lines().collect::<Vec<_>>(),
+            vec![
+                "/ Running",
+                "/0 Running",
+                "/0 Completed",
+                "/1 Running",
+                "/1 Failed",
+                "/ Failed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bad_spec_reports_its_position() {
+        let path = spec_file("bad", "{ \"type\": \"single\" }");
+        let (code, _, err) = run(&[path.to_str().unwrap()]);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(code, 2);
+        assert!(err.ends_with(": line 1, column 1: missing field \"id\"\n"));
+    }
+
+    #[test]
+    fn test_ctl_controls_a_running_batch() {
+        let path = spec_file(
+            "controlled",
+            r#"{ "type": "single", "id": 1, "sleep_ms": 30000 }"#,
+        );
+        let socket = std::env::temp_dir().join(format!("cli-{}.sock", std::process::id()));
+        let batch = {
+            let (path, socket) = (path.clone(), socket.clone());
+            thread::spawn(move || {
+                run(&[
+                    path.to_str().unwrap(),
+                    "--control",
+                    socket.to_str().unwrap(),
+                ])
+            })
+        };
+        let send = |request: &[&str]| {
+            let mut args = vec![socket.to_str().unwrap().to_string()];
+            args.extend(request.iter().map(|word| word.to_string()));
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            let code = ctl(&args, &mut out, &mut err);
+            (
+                code,
+                String::from_utf8(out).unwrap(),
+                String::from_utf8(err).unwrap(),
+            )
+        };
+
+        let wait_for = |listing: &str| {
+            let started = std::time::Instant::now();
+            while send(&["list"]).1 != listing {
+                assert!(started.elapsed() < Duration::from_secs(5));
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+        wait_for("1 Running\n");
+        assert_eq!(
+            send(&["resume", "1"]),
+            (
+                1,
+                String::new(),
+                "only paused jobs can be resumed, not Running\n".to_string()
+            )
+        );
+        // A paused batch keeps waiting until it is stopped.
+        assert_eq!(send(&["pause", "1"]), (0, String::new(), String::new()));
+        wait_for("1 Paused\n");
+        assert!(!batch.is_finished());
+        assert_eq!(send(&["stop", "1"]), (0, String::new(), String::new()));
+
+        let (code, out, err) = batch.join().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((code, out.as_str()), (0, "Stopping single job 1\n"));
+        assert!(err.ends_with("/ Stopped\n"));
+        assert!(!socket.exists());
+        assert_eq!(send(&[]).0, 2);
+    }
+}
@@ -2,6 +2,7 @@ mod abstract_factory;
 mod adapter;
 mod bridge;
 mod builder;
+mod cli;
 mod composite;
 mod factory_method;
 mod singleton;
@@ -9,6 +10,19 @@ mod singleton;
 use factory_method::{AnimalFactory, AnimalFactoryRegistry, BirdFactory, CatFactory, DogFactory};
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("jobs") => {
+            let code = cli::jobs(&args[1..], &mut std::io::stdout(), &mut std::io::stderr());
+            std::process::exit(code);
+        }
+        Some("ctl") => {
+            let code = cli::ctl(&args[1..], &mut std::io::stdout(), &mut std::io::stderr());
+            std::process::exit(code);
+        }
+        _ => {}
+    }
+
     println!("=== Factory Method Pattern Demo ===\n");
 
     println!("1. Using specific factories:");